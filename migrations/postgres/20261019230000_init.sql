@@ -0,0 +1,109 @@
+-- The same schema as the SQLite migrations up to 20261019220000_leader_lease.
+-- Timestamps are stored without a time zone, `created_at` defaults to UTC
+-- like SQLite's CURRENT_TIMESTAMP
+CREATE TABLE endpoint (
+  id TEXT PRIMARY KEY NOT NULL DEFAULT gen_random_uuid()::text,
+  url VARCHAR NOT NULL UNIQUE,
+  status TEXT NOT NULL CHECK (status IN ('UP', 'DOWN', 'PENDING')),
+  uptime_at TIMESTAMP,
+  created_at TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc'),
+  max_latency BIGINT,
+  failures BIGINT NOT NULL DEFAULT 0,
+  successes BIGINT NOT NULL DEFAULT 0,
+  flapping_since TIMESTAMP,
+  unreachable_via VARCHAR,
+  options TEXT NOT NULL DEFAULT '',
+  source TEXT NOT NULL DEFAULT 'env' CHECK (source IN ('env', 'api'))
+);
+
+CREATE TABLE incident (
+  id TEXT PRIMARY KEY NOT NULL DEFAULT gen_random_uuid()::text,
+  url VARCHAR NOT NULL,
+  message TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc'),
+  maintenance BOOLEAN NOT NULL DEFAULT FALSE,
+  reported BOOLEAN NOT NULL DEFAULT FALSE,
+  resolved_at TIMESTAMP,
+  failure TEXT,
+  failure_detail TEXT
+);
+
+CREATE INDEX incident_url_created_at ON incident (url, created_at);
+
+CREATE TABLE metadata (
+  id BIGSERIAL PRIMARY KEY,
+  last_update_sent_at TIMESTAMP,
+  running BOOLEAN NOT NULL DEFAULT FALSE,
+  leader TEXT,
+  leader_until TIMESTAMP
+);
+
+CREATE TABLE certificate (
+  url VARCHAR PRIMARY KEY NOT NULL,
+  expires_at TIMESTAMP NOT NULL,
+  issuer TEXT NOT NULL,
+  sans TEXT NOT NULL,
+  chain_valid BOOLEAN NOT NULL,
+  error TEXT,
+  warned_days BIGINT,
+  checked_at TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE dns_answer (
+  url VARCHAR PRIMARY KEY NOT NULL,
+  answers TEXT NOT NULL,
+  updated_at TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE heartbeat (
+  url VARCHAR PRIMARY KEY NOT NULL,
+  success BOOLEAN NOT NULL,
+  message TEXT,
+  pinged_at TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE status_change (
+  id BIGSERIAL PRIMARY KEY,
+  url VARCHAR NOT NULL,
+  status TEXT NOT NULL CHECK (status IN ('UP', 'DOWN', 'PENDING')),
+  created_at TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+CREATE INDEX status_change_url_created_at ON status_change (url, created_at);
+
+CREATE TABLE check_result (
+  id BIGSERIAL PRIMARY KEY,
+  url VARCHAR NOT NULL,
+  success BOOLEAN NOT NULL,
+  latency BIGINT,
+  maintenance BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc'),
+  dns_latency BIGINT,
+  connect_latency BIGINT,
+  tls_latency BIGINT,
+  ttfb_latency BIGINT,
+  failure TEXT,
+  failure_detail TEXT
+);
+
+CREATE INDEX check_result_url_created_at ON check_result (url, created_at);
+
+CREATE TABLE incident_snapshot (
+  incident_id TEXT PRIMARY KEY NOT NULL REFERENCES incident (id) ON DELETE CASCADE,
+  remote_ip TEXT NOT NULL,
+  status_line TEXT NOT NULL,
+  headers TEXT NOT NULL,
+  body TEXT NOT NULL,
+  truncated BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE location_check (
+  url VARCHAR NOT NULL,
+  location TEXT NOT NULL,
+  success BOOLEAN NOT NULL,
+  latency BIGINT,
+  failure TEXT,
+  failure_detail TEXT,
+  checked_at TIMESTAMP NOT NULL,
+  PRIMARY KEY (url, location)
+);
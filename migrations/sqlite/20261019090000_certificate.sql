@@ -0,0 +1,11 @@
+-- Create the certificate table
+CREATE TABLE certificate (
+  url VARCHAR PRIMARY KEY NOT NULL,
+  expires_at TIMESTAMP NOT NULL,
+  issuer TEXT NOT NULL,
+  sans TEXT NOT NULL,
+  chain_valid BOOLEAN NOT NULL,
+  error TEXT,
+  warned_days INT,
+  checked_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,6 @@
+-- Create the dns_answer table
+CREATE TABLE dns_answer (
+  url VARCHAR PRIMARY KEY NOT NULL,
+  answers TEXT NOT NULL,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
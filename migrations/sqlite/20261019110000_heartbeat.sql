@@ -0,0 +1,7 @@
+-- Create the heartbeat table
+CREATE TABLE heartbeat (
+  url VARCHAR PRIMARY KEY NOT NULL,
+  success BOOLEAN NOT NULL,
+  message TEXT,
+  pinged_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
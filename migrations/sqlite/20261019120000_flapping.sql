@@ -0,0 +1,13 @@
+ALTER TABLE endpoint ADD COLUMN failures INT NOT NULL DEFAULT 0;
+ALTER TABLE endpoint ADD COLUMN successes INT NOT NULL DEFAULT 0;
+ALTER TABLE endpoint ADD COLUMN flapping_since TIMESTAMP;
+
+-- Create the status_change table
+CREATE TABLE status_change (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  url VARCHAR NOT NULL,
+  status TEXT NOT NULL CHECK (status IN ('UP', 'DOWN', 'PENDING')),
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX status_change_url_created_at ON status_change (url, created_at);
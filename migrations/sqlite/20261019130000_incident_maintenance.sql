@@ -0,0 +1 @@
+ALTER TABLE incident ADD COLUMN maintenance BOOLEAN NOT NULL DEFAULT FALSE;
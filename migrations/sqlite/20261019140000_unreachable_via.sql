@@ -0,0 +1 @@
+ALTER TABLE endpoint ADD COLUMN unreachable_via VARCHAR;
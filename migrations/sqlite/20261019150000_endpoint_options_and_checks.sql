@@ -0,0 +1,14 @@
+ALTER TABLE endpoint ADD COLUMN options TEXT NOT NULL DEFAULT '';
+ALTER TABLE endpoint ADD COLUMN source TEXT NOT NULL DEFAULT 'env' CHECK (source IN ('env', 'api'));
+
+-- Create the check_result table
+CREATE TABLE check_result (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  url VARCHAR NOT NULL,
+  success BOOLEAN NOT NULL,
+  latency INT,
+  maintenance BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX check_result_url_created_at ON check_result (url, created_at);
@@ -0,0 +1,5 @@
+-- Incidents are kept after the digest, `reported` marks the ones it included
+ALTER TABLE incident ADD COLUMN reported BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE incident ADD COLUMN resolved_at TIMESTAMP;
+
+CREATE INDEX incident_url_created_at ON incident (url, created_at);
@@ -0,0 +1,2 @@
+-- Set while the monitor runs, still set on boot after an unclean shutdown
+ALTER TABLE metadata ADD COLUMN running BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,5 @@
+-- Milliseconds spent in each phase of an HTTP check
+ALTER TABLE check_result ADD COLUMN dns_latency INT;
+ALTER TABLE check_result ADD COLUMN connect_latency INT;
+ALTER TABLE check_result ADD COLUMN tls_latency INT;
+ALTER TABLE check_result ADD COLUMN ttfb_latency INT;
@@ -0,0 +1,6 @@
+-- Why a check failed, e.g. `timeout`, with a short error detail
+ALTER TABLE check_result ADD COLUMN failure TEXT;
+ALTER TABLE check_result ADD COLUMN failure_detail TEXT;
+
+ALTER TABLE incident ADD COLUMN failure TEXT;
+ALTER TABLE incident ADD COLUMN failure_detail TEXT;
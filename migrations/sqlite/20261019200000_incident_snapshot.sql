@@ -0,0 +1,9 @@
+-- The response that took the endpoint down, kept with its incident
+CREATE TABLE incident_snapshot (
+  incident_id TEXT PRIMARY KEY NOT NULL REFERENCES incident (id) ON DELETE CASCADE,
+  remote_ip TEXT NOT NULL,
+  status_line TEXT NOT NULL,
+  headers TEXT NOT NULL,
+  body TEXT NOT NULL,
+  truncated BOOLEAN NOT NULL DEFAULT FALSE
+);
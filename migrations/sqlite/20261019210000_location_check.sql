@@ -0,0 +1,11 @@
+-- The latest check of each endpoint from every location, agents included
+CREATE TABLE location_check (
+  url VARCHAR NOT NULL,
+  location TEXT NOT NULL,
+  success BOOLEAN NOT NULL,
+  latency INT,
+  failure TEXT,
+  failure_detail TEXT,
+  checked_at TIMESTAMP NOT NULL,
+  PRIMARY KEY (url, location)
+);
@@ -0,0 +1,3 @@
+-- The instance allowed to send notifications when several share the db
+ALTER TABLE metadata ADD COLUMN leader TEXT;
+ALTER TABLE metadata ADD COLUMN leader_until TIMESTAMP;
@@ -0,0 +1,7 @@
+-- Every instance sharing the db tracks its own clean shutdown
+CREATE TABLE instance (
+  id TEXT PRIMARY KEY NOT NULL,
+  running BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+ALTER TABLE metadata DROP COLUMN running;
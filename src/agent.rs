@@ -0,0 +1,266 @@
+use serde::{Deserialize, Serialize};
+use std::{process::ExitCode, sync::Arc, time::Duration};
+use tokio::sync::watch;
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::{
+    check,
+    constants::{
+        get_central_token, get_central_url, get_location, get_max_concurrent_checks,
+        get_shutdown_timeout, get_timeout, get_tries,
+    },
+    db::{endpoint::Failure, url::Url},
+    policy::CheckPolicy,
+    scheduler::Scheduler,
+    shutdown_signal, AGENT_CONNECT_TIMEOUT, AGENT_REFRESH_INTERVAL, AGENT_REQUEST_TIMEOUT,
+};
+
+/// A check sent by an agent to the monitor
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AgentCheck {
+    pub location: String,
+    /// The URL without its options
+    pub url: String,
+    pub success: bool,
+    pub latency: Option<i64>,
+    pub failure: Option<Failure>,
+    pub detail: Option<String>,
+}
+
+/// Talks to the monitor's API with one of its tokens
+struct Central {
+    client: reqwest::Client,
+    url: String,
+    token: String,
+}
+
+impl Central {
+    async fn endpoints(&self) -> anyhow::Result<Vec<Url>> {
+        let urls = self
+            .client
+            .get(format!("{}/api/agent/endpoints", self.url))
+            .bearer_auth(&self.token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<String>>()
+            .await?;
+
+        Ok(urls.into_iter().map(Url::from).collect())
+    }
+
+    async fn report(&self, check: &AgentCheck) -> anyhow::Result<()> {
+        self.client
+            .post(format!("{}/api/agent/checks", self.url))
+            .bearer_auth(&self.token)
+            .json(check)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+/// Runs the checks of the monitor's endpoints from this location and sends
+/// the results back, the monitor decides the status. Nothing is stored and
+/// no alerts are sent from an agent
+pub async fn run() -> anyhow::Result<ExitCode> {
+    let location = Arc::new(get_location());
+
+    // A hung monitor would block the reports and the endpoints refresh
+    let client = reqwest::Client::builder()
+        .connect_timeout(Duration::from_millis(AGENT_CONNECT_TIMEOUT))
+        .timeout(Duration::from_millis(AGENT_REQUEST_TIMEOUT))
+        .build()?;
+    let central = Arc::new(Central {
+        client,
+        url: get_central_url(),
+        token: get_central_token(),
+    });
+
+    info!(
+        location = location.as_str(),
+        central = central.url,
+        "Agent is running"
+    );
+
+    let refresh = Duration::from_millis(AGENT_REFRESH_INTERVAL);
+
+    // The monitor can start after its agents
+    let urls = loop {
+        match central.endpoints().await {
+            Ok(urls) => break urls,
+            Err(e) => {
+                warn!(error = %e, "Couldn't get the endpoints from the monitor, retrying");
+                tokio::select! {
+                    _ = tokio::time::sleep(refresh) => {}
+                    result = shutdown_signal() => {
+                        result?;
+                        return Ok(ExitCode::SUCCESS);
+                    }
+                }
+            }
+        }
+    };
+
+    let (urls_tx, mut urls_rx) = watch::channel(Arc::new(urls));
+    create_endpoints_refresh_cron(urls_tx, Arc::clone(&central));
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut exit_code = ExitCode::SUCCESS;
+
+    loop {
+        let urls = urls_rx.borrow_and_update().clone();
+        let policies = urls
+            .iter()
+            .map(CheckPolicy::from_url)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        info!(endpoints = urls.len(), "Checking the endpoints");
+
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let checks = create_agent_check_cron(
+            urls,
+            policies,
+            Arc::clone(&central),
+            Arc::clone(&location),
+            stop_rx,
+        );
+        tokio::pin!(checks);
+
+        let result = tokio::select! {
+            _ = &mut checks => break,
+            _ = urls_rx.changed() => {
+                info!("The endpoints changed, restarting the checks once the running ones are done");
+                stop_tx.send_replace(true);
+
+                tokio::select! {
+                    _ = &mut checks => continue,
+                    result = &mut shutdown => result,
+                }
+            }
+            result = &mut shutdown => result,
+        };
+
+        result?;
+        info!("Shutting down, waiting for the running checks");
+        stop_tx.send_replace(true);
+
+        let timeout = Duration::from_millis(get_shutdown_timeout());
+        if tokio::time::timeout(timeout, checks).await.is_err() {
+            error!("The running checks didn't finish in time");
+            exit_code = ExitCode::FAILURE;
+        }
+
+        break;
+    }
+
+    info!("Agent stopped");
+
+    Ok(exit_code)
+}
+
+/// Fetches the endpoints again every `AGENT_REFRESH_INTERVAL`, the checks
+/// restart when they changed
+fn create_endpoints_refresh_cron(urls: watch::Sender<Arc<Vec<Url>>>, central: Arc<Central>) {
+    let interval = Duration::from_millis(AGENT_REFRESH_INTERVAL);
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+
+            match central.endpoints().await {
+                Ok(new_urls) => {
+                    // An option change, like the interval, restarts the checks too
+                    let configured = |url: &Url| (url.to_string(), url.options_string());
+                    urls.send_if_modified(|urls| {
+                        let changed = urls
+                            .iter()
+                            .map(configured)
+                            .ne(new_urls.iter().map(configured));
+                        if changed {
+                            *urls = Arc::new(new_urls);
+                        }
+                        changed
+                    });
+                }
+                Err(e) => warn!(error = %e, "Couldn't refresh the endpoints"),
+            }
+        }
+    });
+}
+
+/// Checks every URL at its policy's interval, the agent doesn't know the
+/// status so the down and backoff intervals aren't used
+async fn create_agent_check_cron(
+    urls: Arc<Vec<Url>>,
+    policies: Vec<CheckPolicy>,
+    central: Arc<Central>,
+    location: Arc<String>,
+    shutdown: watch::Receiver<bool>,
+) {
+    let intervals = policies
+        .iter()
+        .map(|policy| policy.interval)
+        .collect::<Vec<_>>();
+    let timeout = get_timeout();
+    let tries = get_tries();
+
+    let scheduler = Scheduler::new(intervals.clone(), get_max_concurrent_checks());
+    scheduler
+        .run(
+            |i| {
+                let urls = Arc::clone(&urls);
+                let central = Arc::clone(&central);
+                let location = Arc::clone(&location);
+                let interval = intervals[i];
+
+                let span = info_span!(
+                    "check",
+                    endpoint = urls[i].name(),
+                    url = urls[i].as_str(),
+                    latency = tracing::field::Empty,
+                    outcome = tracing::field::Empty,
+                );
+
+                async move {
+                    let url = &urls[i];
+
+                    let result = async {
+                        let lookup = check::lookup(url, timeout, tries).await?;
+                        let outcome = if lookup.is_success {
+                            "success"
+                        } else {
+                            "failure"
+                        };
+                        tracing::Span::current()
+                            .record("outcome", outcome)
+                            .record("latency", lookup.latency);
+
+                        central
+                            .report(&AgentCheck {
+                                location: location.to_string(),
+                                url: url.as_str().to_string(),
+                                success: lookup.is_success,
+                                latency: lookup.latency,
+                                failure: lookup.failure,
+                                detail: lookup.detail,
+                            })
+                            .await
+                    }
+                    .await;
+
+                    if let Err(e) = result {
+                        error!(error = %e, "Check failed");
+                    }
+
+                    interval
+                }
+                .instrument(span)
+            },
+            shutdown,
+        )
+        .await;
+}
@@ -1,11 +1,15 @@
 use teloxide::{prelude::*, RequestError};
 
+use crate::metrics::METRICS;
+use tracing::{error, info};
+
 pub const TELEGRAM_MAX_MESSAGE_LENGTH: usize = 4096;
 
 pub fn create_bot() -> Bot {
     Bot::from_env()
 }
 
+#[tracing::instrument(name = "notification", skip_all, fields(length = opts.message.len()))]
 pub async fn notify<'a>(opts: &NotifyOpts<'a>) -> anyhow::Result<Vec<Message>, RequestError> {
     let telegram_chat_id = std::env::var("TELEGRAM_CHAT_ID").expect("TELEGRAM_CHAT_ID must be set");
 
@@ -15,10 +19,19 @@ pub async fn notify<'a>(opts: &NotifyOpts<'a>) -> anyhow::Result<Vec<Message>, R
     let mut results = Vec::new();
     for message in messages.into_iter() {
         let telegram_chat_id = telegram_chat_id.clone();
-        let result = opts.bot.send_message(telegram_chat_id, &message).await?;
+        let result = opts
+            .bot
+            .send_message(telegram_chat_id, &message)
+            .await
+            .inspect_err(|e| {
+                METRICS.record_notification_failure();
+                error!(error = %e, "Couldn't send the notification");
+            })?;
         results.push(result);
     }
 
+    info!(messages = results.len(), "Notification sent");
+
     Ok(results)
 }
 
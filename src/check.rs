@@ -0,0 +1,170 @@
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+
+use crate::{
+    db::{
+        endpoint::{Failure, Lookup},
+        url::Url,
+    },
+    dns::{self, DnsCheck},
+    probe, tls,
+};
+
+/// Checks the URL up to `tries` times and returns the first successful
+/// lookup, or the last failed one. Nothing is stored so agents can run the
+/// checks without a database, heartbeats are only received by the monitor
+pub async fn lookup(url: &Url, timeout: Duration, tries: u8) -> anyhow::Result<Lookup> {
+    let mut lookup = Lookup::default();
+
+    for attempt in 1..=tries {
+        lookup = send_request(url, timeout)
+            .instrument(tracing::debug_span!("attempt", attempt))
+            .await?;
+
+        tracing::debug!(
+            attempt,
+            success = lookup.is_success,
+            latency = lookup.latency,
+            "Attempt completed"
+        );
+
+        if lookup.is_success {
+            return Ok(lookup);
+        }
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    Ok(lookup)
+}
+
+async fn send_request(url: &Url, timeout: Duration) -> anyhow::Result<Lookup> {
+    if url.is_heartbeat() {
+        anyhow::bail!("{} is a heartbeat, it can't be checked", url.name());
+    }
+
+    if url.is_tls_service() {
+        return Ok(send_handshake(url, timeout).await);
+    }
+
+    if url.is_dns() {
+        return send_dns_query(url, timeout).await;
+    }
+
+    let start = Instant::now();
+    let probe = probe::get(url.as_str(), timeout).await;
+    let latency = start.elapsed().as_millis() as i64;
+
+    let lookup = match probe.result {
+        Ok(status) if probe::is_up(status) => Lookup {
+            is_success: true,
+            ..Default::default()
+        },
+        Ok(status) => Lookup {
+            failure: Some(Failure::Http),
+            detail: Some(status.to_string()),
+            snapshot: probe.snapshot,
+            ..Default::default()
+        },
+        Err(e) => Lookup {
+            failure: Some(e.failure),
+            detail: Some(e.detail),
+            snapshot: probe.snapshot,
+            ..Default::default()
+        },
+    };
+
+    // The body isn't part of the latency
+    Ok(Lookup {
+        latency: Some(probe.latency.unwrap_or(latency)),
+        timings: Some(probe.timings),
+        ..lookup
+    })
+}
+
+/// A TLS service is up when the handshake completes with a valid chain
+async fn send_handshake(url: &Url, timeout: Duration) -> Lookup {
+    let start = Instant::now();
+    let res = match url.tls_address() {
+        Some((host, port)) => tls::inspect(&host, port, timeout).await,
+        None => Err(anyhow::anyhow!("{} doesn't use TLS", url)),
+    };
+    let latency = start.elapsed().as_millis() as i64;
+
+    let lookup = match res {
+        Ok(info) if info.chain_valid => Lookup {
+            is_success: true,
+            ..Default::default()
+        },
+        Ok(info) => Lookup {
+            failure: Some(Failure::Tls),
+            detail: info.error,
+            ..Default::default()
+        },
+        Err(e) => Lookup {
+            failure: Some(classify_handshake_error(&e)),
+            detail: Some(e.to_string()),
+            ..Default::default()
+        },
+    };
+
+    Lookup {
+        latency: Some(latency),
+        ..lookup
+    }
+}
+
+/// A DNS monitor is up when the answers contain the expected values,
+/// the resolution time is recorded as the latency
+async fn send_dns_query(url: &Url, timeout: Duration) -> anyhow::Result<Lookup> {
+    let check = DnsCheck::parse(url)?;
+
+    let start = Instant::now();
+    let res = dns::resolve(&check, timeout).await;
+    let latency = start.elapsed().as_millis() as i64;
+
+    let lookup = match res {
+        Ok(answers) if check.matches(&answers) => Lookup {
+            is_success: true,
+            answers: Some(answers),
+            latency: Some(latency),
+            ..Default::default()
+        },
+        Ok(answers) => Lookup {
+            failure: Some(Failure::Assertion),
+            detail: Some(format!("Unexpected answers: {}", format_answers(&answers))),
+            answers: Some(answers),
+            latency: Some(latency),
+            ..Default::default()
+        },
+        Err(e) => Lookup {
+            failure: Some(Failure::Dns),
+            detail: Some(e.to_string()),
+            latency: Some(latency),
+            ..Default::default()
+        },
+    };
+
+    Ok(lookup)
+}
+
+/// Connection errors and TLS errors are both I/O errors during a handshake
+fn classify_handshake_error(error: &anyhow::Error) -> Failure {
+    if error.is::<tokio::time::error::Elapsed>() {
+        return Failure::Timeout;
+    }
+
+    match error.downcast_ref::<std::io::Error>().map(|e| e.kind()) {
+        Some(std::io::ErrorKind::ConnectionRefused) => Failure::ConnectionRefused,
+        Some(std::io::ErrorKind::InvalidData) => Failure::Tls,
+        _ => Failure::Connection,
+    }
+}
+
+fn format_answers(answers: &[String]) -> String {
+    if answers.is_empty() {
+        "none".to_string()
+    } else {
+        answers.join(", ")
+    }
+}
@@ -1,8 +1,281 @@
-use crate::DEFAULT_INTERVAL;
+use std::{sync::LazyLock, time::Duration};
+
+use crate::{
+    DEFAULT_CERT_CHECK_INTERVAL, DEFAULT_CERT_WARN_DAYS, DEFAULT_DATABASE_URL,
+    DEFAULT_DOWN_THRESHOLD, DEFAULT_FLAP_THRESHOLD, DEFAULT_FLAP_WINDOW, DEFAULT_INTERVAL,
+    DEFAULT_LEADER_LEASE, DEFAULT_LOCATION, DEFAULT_MAX_BODY_SIZE, DEFAULT_MAX_CONCURRENT_CHECKS,
+    DEFAULT_MONITOR_HEARTBEAT_INTERVAL, DEFAULT_OTEL_SERVICE_NAME, DEFAULT_SHUTDOWN_TIMEOUT,
+    DEFAULT_SNAPSHOT_BODY_SIZE, DEFAULT_STATUS_PAGE_COLOR, DEFAULT_STATUS_PAGE_TITLE,
+    DEFAULT_TIMEOUT, DEFAULT_TRIES, DEFAULT_UP_THRESHOLD,
+};
 
 pub fn get_interval() -> u64 {
-    std::env::var("INTERVAL")
+    let interval = std::env::var("INTERVAL")
         .unwrap_or_else(|_| DEFAULT_INTERVAL.to_string())
         .parse()
-        .expect("INTERVAL must be a number")
+        .expect("INTERVAL must be a number");
+
+    if interval < 1 {
+        panic!("INTERVAL must be greater than 0");
+    }
+
+    interval
+}
+
+/// How long a single check attempt can take
+pub fn get_timeout() -> Duration {
+    let seconds = std::env::var("TIMEOUT")
+        .unwrap_or_else(|_| DEFAULT_TIMEOUT.to_string())
+        .parse()
+        .expect("TIMEOUT must be a number");
+
+    Duration::from_secs(seconds)
+}
+
+/// Attempts of a check before it fails
+pub fn get_tries() -> u8 {
+    let tries = std::env::var("TRIES")
+        .unwrap_or_else(|_| DEFAULT_TRIES.to_string())
+        .parse()
+        .expect("TRIES must be a number");
+
+    if tries < 1 {
+        panic!("TRIES must be greater than 0");
+    }
+
+    tries
+}
+
+/// How many checks can run at the same time
+pub fn get_max_concurrent_checks() -> usize {
+    let max = std::env::var("MAX_CONCURRENT_CHECKS")
+        .unwrap_or_else(|_| DEFAULT_MAX_CONCURRENT_CHECKS.to_string())
+        .parse()
+        .expect("MAX_CONCURRENT_CHECKS must be a number");
+
+    // No check would ever get a slot
+    if max < 1 {
+        panic!("MAX_CONCURRENT_CHECKS must be greater than 0");
+    }
+
+    max
+}
+
+pub fn get_cert_check_interval() -> u64 {
+    std::env::var("CERT_CHECK_INTERVAL")
+        .unwrap_or_else(|_| DEFAULT_CERT_CHECK_INTERVAL.to_string())
+        .parse()
+        .expect("CERT_CHECK_INTERVAL must be a number")
+}
+
+/// Days before a certificate expires at which a warning is sent
+pub fn get_cert_warn_days() -> Vec<i64> {
+    std::env::var("CERT_WARN_DAYS")
+        .unwrap_or_else(|_| DEFAULT_CERT_WARN_DAYS.to_string())
+        .split(',')
+        .map(|days| {
+            days.trim()
+                .parse()
+                .expect("CERT_WARN_DAYS must be comma separated numbers")
+        })
+        .collect()
+}
+
+/// Consecutive failed checks before an endpoint is marked as down
+pub fn get_down_threshold() -> i64 {
+    std::env::var("DOWN_THRESHOLD")
+        .unwrap_or_else(|_| DEFAULT_DOWN_THRESHOLD.to_string())
+        .parse()
+        .expect("DOWN_THRESHOLD must be a number")
+}
+
+/// Consecutive successful checks before an endpoint is marked as up
+pub fn get_up_threshold() -> i64 {
+    std::env::var("UP_THRESHOLD")
+        .unwrap_or_else(|_| DEFAULT_UP_THRESHOLD.to_string())
+        .parse()
+        .expect("UP_THRESHOLD must be a number")
+}
+
+/// Status changes within the flap window for an endpoint to be flapping,
+/// `0` disables flap detection
+pub fn get_flap_threshold() -> i64 {
+    std::env::var("FLAP_THRESHOLD")
+        .unwrap_or_else(|_| DEFAULT_FLAP_THRESHOLD.to_string())
+        .parse()
+        .expect("FLAP_THRESHOLD must be a number")
+}
+
+pub fn get_flap_window() -> u64 {
+    std::env::var("FLAP_WINDOW")
+        .unwrap_or_else(|_| DEFAULT_FLAP_WINDOW.to_string())
+        .parse()
+        .expect("FLAP_WINDOW must be a number")
+}
+
+/// Bearer tokens for the API from the comma separated `API_TOKENS`
+pub fn get_api_tokens() -> Vec<String> {
+    std::env::var("API_TOKENS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+pub fn get_status_page_title() -> String {
+    std::env::var("STATUS_PAGE_TITLE").unwrap_or_else(|_| DEFAULT_STATUS_PAGE_TITLE.to_string())
+}
+
+/// URL of the logo shown on the status page
+pub fn get_status_page_logo() -> Option<String> {
+    std::env::var("STATUS_PAGE_LOGO")
+        .ok()
+        .filter(|logo| !logo.is_empty())
+}
+
+/// Accent color of the status page, any CSS color
+pub fn get_status_page_color() -> String {
+    std::env::var("STATUS_PAGE_COLOR").unwrap_or_else(|_| DEFAULT_STATUS_PAGE_COLOR.to_string())
+}
+
+/// URL pinged while the monitor is healthy, for an external dead man's switch
+pub fn get_monitor_heartbeat_url() -> Option<String> {
+    std::env::var("MONITOR_HEARTBEAT_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+}
+
+pub fn get_monitor_heartbeat_interval() -> u64 {
+    std::env::var("MONITOR_HEARTBEAT_INTERVAL")
+        .unwrap_or_else(|_| DEFAULT_MONITOR_HEARTBEAT_INTERVAL.to_string())
+        .parse()
+        .expect("MONITOR_HEARTBEAT_INTERVAL must be a number")
+}
+
+/// How long to wait for the running checks when shutting down
+pub fn get_shutdown_timeout() -> u64 {
+    std::env::var("SHUTDOWN_TIMEOUT")
+        .unwrap_or_else(|_| DEFAULT_SHUTDOWN_TIMEOUT.to_string())
+        .parse()
+        .expect("SHUTDOWN_TIMEOUT must be a number")
+}
+
+/// Read on every HTTP check, so they're parsed once
+static MAX_BODY_SIZE: LazyLock<usize> = LazyLock::new(|| {
+    let size = std::env::var("MAX_BODY_SIZE")
+        .unwrap_or_else(|_| DEFAULT_MAX_BODY_SIZE.to_string())
+        .parse()
+        .expect("MAX_BODY_SIZE must be a number");
+    assert!(size > 0, "MAX_BODY_SIZE must be greater than 0");
+    size
+});
+
+static SNAPSHOT_BODY_SIZE: LazyLock<usize> = LazyLock::new(|| {
+    std::env::var("SNAPSHOT_BODY_SIZE")
+        .unwrap_or_else(|_| DEFAULT_SNAPSHOT_BODY_SIZE.to_string())
+        .parse()
+        .expect("SNAPSHOT_BODY_SIZE must be a number")
+});
+
+/// Largest `Content-Length` an HTTP response can declare before the check
+/// fails
+pub fn get_max_body_size() -> usize {
+    *MAX_BODY_SIZE
+}
+
+/// Bytes of the failing response body kept with an incident
+pub fn get_snapshot_body_size() -> usize {
+    *SNAPSHOT_BODY_SIZE
+}
+
+/// The OTLP collector, traces and metrics are exported when it's set
+pub fn get_otlp_endpoint() -> Option<String> {
+    std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .ok()
+        .filter(|endpoint| !endpoint.is_empty())
+}
+
+pub fn get_otel_service_name() -> String {
+    std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| DEFAULT_OTEL_SERVICE_NAME.to_string())
+}
+
+/// `MODE=agent` runs the checks for a central monitor instead of monitoring
+pub fn is_agent() -> bool {
+    std::env::var("MODE").is_ok_and(|mode| mode == "agent")
+}
+
+/// Name of the location the checks run from, agents must set it
+pub fn get_location() -> String {
+    if is_agent() {
+        return std::env::var("LOCATION").expect("LOCATION must be set for agents");
+    }
+
+    std::env::var("LOCATION").unwrap_or_else(|_| DEFAULT_LOCATION.to_string())
+}
+
+/// The monitor an agent reports to, e.g. `https://monitor.example.com`
+pub fn get_central_url() -> String {
+    std::env::var("CENTRAL_URL")
+        .expect("CENTRAL_URL must be set for agents")
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// One of the central monitor's `API_TOKENS`
+pub fn get_central_token() -> String {
+    std::env::var("CENTRAL_TOKEN").expect("CENTRAL_TOKEN must be set for agents")
+}
+
+/// Failing locations needed to count a check as failed, a majority of the
+/// locations when it's not set
+pub fn get_quorum() -> Option<usize> {
+    std::env::var("QUORUM").ok().map(|quorum| {
+        let quorum = quorum.parse().expect("QUORUM must be a number");
+
+        if quorum < 1 {
+            panic!("QUORUM must be greater than 0");
+        }
+
+        quorum
+    })
+}
+
+/// Identifies the instance holding the leader lease, the host name and the
+/// process id by default
+pub fn get_instance_id() -> String {
+    std::env::var("INSTANCE_ID")
+        .ok()
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| {
+            let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "monitor".to_string());
+            format!("{}-{}", host, std::process::id())
+        })
+}
+
+/// Identifies the instance across restarts for the unclean shutdown notice,
+/// unlike the default instance id which has the process id
+pub fn get_instance_name() -> String {
+    std::env::var("INSTANCE_ID")
+        .ok()
+        .filter(|id| !id.is_empty())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .unwrap_or_else(|| "monitor".to_string())
+}
+
+/// How long the leader lease lasts without being renewed
+pub fn get_leader_lease() -> u64 {
+    std::env::var("LEADER_LEASE")
+        .unwrap_or_else(|_| DEFAULT_LEADER_LEASE.to_string())
+        .parse()
+        .expect("LEADER_LEASE must be a number")
+}
+
+/// A `sqlite:` URL with the path of the database file, `sqlite::memory:`
+/// for a database that's gone on exit, or a `postgres://` URL
+pub fn get_database_url() -> String {
+    std::env::var("DATABASE_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| DEFAULT_DATABASE_URL.to_string())
 }
@@ -0,0 +1,105 @@
+use chrono::{Local, NaiveDateTime};
+
+use super::{run, Connection};
+use crate::tls::CertificateInfo;
+
+#[derive(Debug, sqlx::FromRow)]
+#[allow(unused)]
+pub struct Certificate {
+    pub url: String,
+    pub expires_at: NaiveDateTime,
+    pub issuer: String,
+    pub sans: String,
+    pub chain_valid: bool,
+    pub error: Option<String>,
+    pub warned_days: Option<i64>,
+    pub checked_at: NaiveDateTime,
+}
+
+impl Certificate {
+    pub fn days_left(&self) -> i64 {
+        let now = Local::now().naive_local();
+        self.expires_at.signed_duration_since(now).num_days()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at < Local::now().naive_local()
+    }
+}
+
+#[derive(Debug)]
+pub struct CertificateModel {
+    pool: Connection,
+}
+
+impl CertificateModel {
+    pub fn new(pool: Connection) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get_all(&self) -> anyhow::Result<Vec<Certificate>> {
+        let certificates = run!(
+            self.pool,
+            sqlx::query_as("SELECT * FROM certificate ORDER BY expires_at ASC"),
+            fetch_all
+        )?;
+
+        Ok(certificates)
+    }
+
+    pub async fn get(&self, url: &str) -> anyhow::Result<Option<Certificate>> {
+        let certificate = run!(
+            self.pool,
+            sqlx::query_as("SELECT * FROM certificate WHERE url = $1").bind(url),
+            fetch_optional
+        )?;
+
+        Ok(certificate)
+    }
+
+    /// Stores the latest certificate details, the warning state is reset
+    /// when the certificate was renewed.
+    pub async fn upsert(&self, url: &str, info: &CertificateInfo) -> anyhow::Result<()> {
+        let sans = info.sans.join(", ");
+        let now = Local::now().naive_local();
+
+        run!(
+            self.pool,
+            sqlx::query(
+                "INSERT INTO certificate (url, expires_at, issuer, sans, chain_valid, error, checked_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (url) DO UPDATE SET
+                    warned_days = CASE WHEN certificate.expires_at = excluded.expires_at
+                        THEN certificate.warned_days ELSE NULL END,
+                    expires_at = excluded.expires_at,
+                    issuer = excluded.issuer,
+                    sans = excluded.sans,
+                    chain_valid = excluded.chain_valid,
+                    error = excluded.error,
+                    checked_at = excluded.checked_at"
+            )
+            .bind(url)
+            .bind(info.expires_at)
+            .bind(&info.issuer)
+            .bind(&sans)
+            .bind(info.chain_valid)
+            .bind(&info.error)
+            .bind(now),
+            execute
+        )?;
+
+        Ok(())
+    }
+
+    pub async fn set_warned_days(&self, url: &str, days: i64) -> anyhow::Result<()> {
+        run!(
+            self.pool,
+            sqlx::query("UPDATE certificate SET warned_days = $1 WHERE url = $2")
+                .bind(days)
+                .bind(url),
+            execute
+        )?;
+
+        Ok(())
+    }
+}
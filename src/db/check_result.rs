@@ -0,0 +1,143 @@
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use serde::Serialize;
+
+use super::{
+    endpoint::{Failure, Lookup},
+    run, Connection,
+};
+use crate::health::HEALTH;
+
+/// The outcome of a single check, kept for the history of an endpoint
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[allow(unused)]
+pub struct CheckResult {
+    pub id: i64,
+    pub url: String,
+    pub success: bool,
+    pub latency: Option<i64>,
+    /// Ran during a maintenance window
+    pub maintenance: bool,
+    pub created_at: NaiveDateTime,
+    /// Milliseconds spent in each phase of an HTTP check
+    pub dns_latency: Option<i64>,
+    pub connect_latency: Option<i64>,
+    pub tls_latency: Option<i64>,
+    pub ttfb_latency: Option<i64>,
+    pub failure: Option<Failure>,
+    pub failure_detail: Option<String>,
+}
+
+/// The checks of a URL on one day, without the ones during maintenance
+#[derive(Debug, sqlx::FromRow)]
+pub struct DailyUptime {
+    pub day: NaiveDate,
+    pub checks: i64,
+    pub successes: i64,
+}
+
+impl DailyUptime {
+    pub fn percentage(&self) -> f64 {
+        self.successes as f64 * 100.0 / self.checks as f64
+    }
+}
+
+#[derive(Debug)]
+pub struct CheckResultModel {
+    pool: Connection,
+}
+
+impl CheckResultModel {
+    pub fn new(pool: Connection) -> Self {
+        Self { pool }
+    }
+
+    pub async fn add(&self, url: &str, lookup: &Lookup, maintenance: bool) -> anyhow::Result<()> {
+        let now = Local::now().naive_local();
+        let timings = lookup.timings.unwrap_or_default();
+
+        run!(
+            self.pool,
+            sqlx::query(
+                "INSERT INTO check_result (
+                    url, success, latency, maintenance, created_at,
+                    dns_latency, connect_latency, tls_latency, ttfb_latency,
+                    failure, failure_detail
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)"
+            )
+            .bind(url)
+            .bind(lookup.is_success)
+            .bind(lookup.latency)
+            .bind(maintenance)
+            .bind(now)
+            .bind(timings.dns)
+            .bind(timings.connect)
+            .bind(timings.tls)
+            .bind(timings.ttfb)
+            .bind(lookup.failure)
+            .bind(&lookup.detail),
+            execute
+        )?;
+
+        HEALTH.record_db_write();
+
+        Ok(())
+    }
+
+    /// The latest checks of the URL, newest first
+    pub async fn get_recent(&self, url: &str, limit: i64) -> anyhow::Result<Vec<CheckResult>> {
+        let checks = run!(
+            self.pool,
+            sqlx::query_as(
+                "SELECT * FROM check_result WHERE url = $1 ORDER BY created_at DESC LIMIT $2"
+            )
+            .bind(url)
+            .bind(limit),
+            fetch_all
+        )?;
+
+        Ok(checks)
+    }
+
+    /// The uptime of the URL per day since `since`, oldest first. Days
+    /// without checks are left out
+    pub async fn daily_uptime(
+        &self,
+        url: &str,
+        since: NaiveDateTime,
+    ) -> anyhow::Result<Vec<DailyUptime>> {
+        // SQLite stores the timestamps as text
+        let day = match self.pool {
+            Connection::Sqlite(_) => "date(created_at)",
+            Connection::Postgres(_) => "CAST(created_at AS DATE)",
+        };
+        let query = format!(
+            "SELECT
+                {day} as day,
+                COUNT(*) as checks,
+                SUM(CASE WHEN success THEN 1 ELSE 0 END) as successes
+            FROM check_result
+            WHERE url = $1 AND created_at >= $2 AND maintenance = FALSE
+            GROUP BY {day}
+            ORDER BY {day}"
+        );
+
+        let days = run!(
+            self.pool,
+            sqlx::query_as(&query).bind(url).bind(since),
+            fetch_all
+        )?;
+
+        Ok(days)
+    }
+
+    /// Deletes the checks that are older than `before`
+    pub async fn delete_before(&self, before: NaiveDateTime) -> anyhow::Result<()> {
+        run!(
+            self.pool,
+            sqlx::query("DELETE FROM check_result WHERE created_at < $1").bind(before),
+            execute
+        )?;
+
+        Ok(())
+    }
+}
@@ -1,48 +1,120 @@
 use chrono::Local;
-use sqlx::{Pool, Sqlite};
+use sqlx::{PgPool, SqlitePool};
+use tokio::sync::watch;
+use tracing::debug;
 
 use super::{
-    endpoint::EndpointModel,
-    helpers::{connect, create_db_if_not_exists, migrate},
+    certificate::CertificateModel,
+    check_result::CheckResultModel,
+    dns::DnsAnswerModel,
+    endpoint::{EndpointModel, Lookup},
+    heartbeat::HeartbeatModel,
+    helpers::{connect, migrate, warn_about_previous_database},
     incident::IncidentModel,
+    location_check::LocationCheckModel,
     metadata::MetadataModel,
+    run,
+    snapshot::SnapshotModel,
+    status_change::StatusChangeModel,
     url::Url,
 };
+use crate::{constants::get_database_url, metrics::METRICS};
 
-pub type Connection = Pool<Sqlite>;
+/// The pool of the backend selected by `DATABASE_URL`, queries are run on it
+/// with [`run!`](super::run)
+#[derive(Debug, Clone)]
+pub enum Connection {
+    Sqlite(SqlitePool),
+    Postgres(PgPool),
+}
+
+impl Connection {
+    pub async fn close(&self) {
+        match self {
+            Connection::Sqlite(pool) => pool.close().await,
+            Connection::Postgres(pool) => pool.close().await,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Db {
-    pub verbose: bool,
     pub pool: Connection,
     pub endpoint: EndpointModel,
     pub incident: IncidentModel,
     pub metadata: MetadataModel,
+    pub certificate: CertificateModel,
+    pub dns_answer: DnsAnswerModel,
+    pub heartbeat: HeartbeatModel,
+    pub status_change: StatusChangeModel,
+    pub check_result: CheckResultModel,
+    pub snapshot: SnapshotModel,
+    pub location_check: LocationCheckModel,
+    /// Notified whenever an endpoint goes up or down
+    pub status_changes: watch::Sender<()>,
 }
 
 impl Db {
-    pub async fn new(urls: &Vec<Url>) -> anyhow::Result<Self> {
-        let verbose = false;
+    /// Connects to `DATABASE_URL`, the SQLite database in `db/` by default
+    pub async fn new(urls: &[Url]) -> anyhow::Result<Self> {
+        let db_url = get_database_url();
+        warn_about_previous_database(&db_url);
+
+        Self::connect(&db_url, urls).await
+    }
+
+    /// A fresh database that's dropped with its pool
+    #[cfg(test)]
+    pub async fn in_memory(urls: &[Url]) -> anyhow::Result<Self> {
+        Self::connect("sqlite::memory:", urls).await
+    }
 
-        // create the db file if not exists
-        create_db_if_not_exists()?;
+    /// A fresh database on `db_url`, the PostgreSQL schema is dropped first
+    #[cfg(test)]
+    pub async fn fresh(db_url: &str, urls: &[Url]) -> anyhow::Result<Self> {
+        if let Connection::Postgres(pool) = connect(db_url).await? {
+            sqlx::query("DROP SCHEMA public CASCADE")
+                .execute(&pool)
+                .await?;
+            sqlx::query("CREATE SCHEMA public").execute(&pool).await?;
+            pool.close().await;
+        }
+
+        Self::connect(db_url, urls).await
+    }
 
-        // connect the db
-        let pool = connect(verbose).await?;
+    async fn connect(db_url: &str, urls: &[Url]) -> anyhow::Result<Self> {
+        // connect the db, SQLite creates the file if it doesn't exist
+        let pool = connect(db_url).await?;
 
         // run the migrations
-        migrate(&pool, verbose).await?;
+        migrate(&pool).await?;
 
         let incident = IncidentModel::new(pool.clone());
         let endpoint = EndpointModel::new(pool.clone(), urls).await?;
         let metadata = MetadataModel::new(pool.clone()).await?;
+        let certificate = CertificateModel::new(pool.clone());
+        let dns_answer = DnsAnswerModel::new(pool.clone());
+        let heartbeat = HeartbeatModel::new(pool.clone());
+        let status_change = StatusChangeModel::new(pool.clone());
+        let check_result = CheckResultModel::new(pool.clone());
+        let snapshot = SnapshotModel::new(pool.clone());
+        let location_check = LocationCheckModel::new(pool.clone());
+        let (status_changes, _) = watch::channel(());
 
         let db = Self {
-            verbose,
             pool,
             incident,
             endpoint,
             metadata,
+            certificate,
+            dns_answer,
+            heartbeat,
+            status_change,
+            check_result,
+            snapshot,
+            location_check,
+            status_changes,
         };
 
         Ok(db)
@@ -50,46 +122,193 @@ impl Db {
 
     pub async fn set_status_up(&self, url: &str) -> anyhow::Result<()> {
         // Update the database
-        let now = Local::now();
-        sqlx::query!(
-            "UPDATE endpoint SET status = 'UP', uptime_at = ? WHERE url = ?",
-            now,
-            url
-        )
-        .execute(&self.pool)
-        .await?;
-
-        if self.verbose {
-            println!("{} is up!", url);
+        let now = Local::now().naive_local();
+        run!(
+            self.pool,
+            sqlx::query(
+                "UPDATE endpoint SET status = 'UP', uptime_at = COALESCE(uptime_at, $1), unreachable_via = NULL WHERE url = $2"
+            )
+            .bind(now)
+            .bind(url),
+            execute
+        )?;
+
+        self.incident.resolve(url).await?;
+        self.status_changes.send_replace(());
+
+        debug!(url, "Marked as up");
+
+        Ok(())
+    }
+
+    /// The incident of a downtime during a maintenance window is flagged so
+    /// it's left out of the history's downtime
+    pub async fn set_status_down(
+        &self,
+        url: &Url,
+        lookup: &Lookup,
+        maintenance: bool,
+    ) -> anyhow::Result<()> {
+        let message = format!("{} was down!", url.strip_prefix());
+        self.set_down(url, &message, lookup, None, maintenance)
+            .await
+    }
+
+    /// Marks the URL as down because an endpoint it depends on is down
+    pub async fn set_status_unreachable(
+        &self,
+        url: &Url,
+        lookup: &Lookup,
+        parent: &Url,
+        maintenance: bool,
+    ) -> anyhow::Result<()> {
+        let message = format!(
+            "{} was unreachable due to {}",
+            url.strip_prefix(),
+            parent.name()
+        );
+        self.set_down(url, &message, lookup, Some(parent), maintenance)
+            .await
+    }
+
+    /// The parent of an unreachable URL is up again but the URL is still
+    /// down, its open incident becomes a down one instead of opening another
+    pub async fn set_status_down_from_unreachable(
+        &self,
+        url: &Url,
+        lookup: &Lookup,
+        maintenance: bool,
+    ) -> anyhow::Result<()> {
+        let Some(incident) = self.incident.get_open(url).await? else {
+            return self.set_status_down(url, lookup, maintenance).await;
+        };
+
+        run!(
+            self.pool,
+            sqlx::query("UPDATE endpoint SET unreachable_via = NULL WHERE url = $1")
+                .bind(url.as_str()),
+            execute
+        )?;
+
+        let message = format!("{} was down!", url.strip_prefix());
+        self.incident
+            .set_cause(
+                &incident.id,
+                &message,
+                lookup.failure,
+                lookup.detail.as_deref(),
+            )
+            .await?;
+
+        if let Some(snapshot) = &lookup.snapshot {
+            if self.snapshot.get(&incident.id).await?.is_none() {
+                self.snapshot.add(&incident.id, snapshot).await?;
+            }
         }
 
+        self.status_changes.send_replace(());
+
+        debug!(url = url.as_str(), "Marked as down instead of unreachable");
+
         Ok(())
     }
 
-    pub async fn set_status_down(&self, url: &Url) -> anyhow::Result<()> {
+    async fn set_down(
+        &self,
+        url: &Url,
+        message: &str,
+        lookup: &Lookup,
+        unreachable_via: Option<&Url>,
+        maintenance: bool,
+    ) -> anyhow::Result<()> {
         let url_str = url.as_str();
-        sqlx::query!(
-            "UPDATE endpoint SET status = 'DOWN', uptime_at = NULL WHERE url = ?",
-            url_str
-        )
-        .execute(&self.pool)
-        .await?;
+        let unreachable_via = unreachable_via.map(|parent| parent.as_str());
+        run!(
+            self.pool,
+            sqlx::query(
+                "UPDATE endpoint SET
+                    status = 'DOWN',
+                    uptime_at = NULL,
+                    unreachable_via = $1
+                WHERE url = $2"
+            )
+            .bind(unreachable_via)
+            .bind(url_str),
+            execute
+        )?;
 
-        let message = format!("{} was down!", url.strip_prefix());
-        let created_at = Local::now();
-        sqlx::query!(
-            "INSERT INTO incident (url, message, created_at) VALUES (?, ?, ?)",
-            url_str,
-            message,
-            created_at
-        )
-        .execute(&self.pool)
-        .await?;
-
-        if self.verbose {
-            println!("{}", message);
+        let created_at = Local::now().naive_local();
+        let incident_id: String = run!(
+            self.pool,
+            sqlx::query_scalar(
+                "INSERT INTO incident (url, message, created_at, maintenance, failure, failure_detail)
+                VALUES ($1, $2, $3, $4, $5, $6) RETURNING id"
+            )
+            .bind(url_str)
+            .bind(message)
+            .bind(created_at)
+            .bind(maintenance)
+            .bind(lookup.failure)
+            .bind(&lookup.detail),
+            fetch_one
+        )?;
+
+        if let Some(snapshot) = &lookup.snapshot {
+            self.snapshot.add(&incident_id, snapshot).await?;
         }
 
+        METRICS.record_incident(url);
+        self.status_changes.send_replace(());
+
+        debug!(url = url_str, message, "Marked as down");
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::endpoint::{Failure, Status};
+
+    #[tokio::test]
+    async fn test_down_and_up_open_and_resolve_an_incident() {
+        let url = Url::from("https://example.com#name=example".to_string());
+        let db = Db::in_memory(std::slice::from_ref(&url)).await.unwrap();
+
+        let lookup = Lookup {
+            failure: Some(Failure::Timeout),
+            detail: Some("No response after 10s".to_string()),
+            ..Default::default()
+        };
+        db.set_status_down(&url, &lookup, false).await.unwrap();
+
+        assert_eq!(db.endpoint.get(&url).await.unwrap().status, Status::Down);
+        let incidents = db.incident.get_unreported().await.unwrap();
+        assert_eq!(incidents.len(), 1);
+        assert_eq!(incidents[0].failure, Some(Failure::Timeout));
+        assert!(incidents[0].resolved_at.is_none());
+
+        db.set_status_up(&url).await.unwrap();
+
+        assert_eq!(db.endpoint.get(&url).await.unwrap().status, Status::Up);
+        let incidents = db.incident.get_unreported().await.unwrap();
+        assert!(incidents[0].resolved_at.is_some());
+    }
+    #[tokio::test]
+    async fn test_down_during_maintenance_resets_the_uptime() {
+        let url = Url::from("https://example.com#name=example".to_string());
+        let db = Db::in_memory(std::slice::from_ref(&url)).await.unwrap();
+
+        db.set_status_up(&url).await.unwrap();
+        assert!(db.endpoint.get(&url).await.unwrap().uptime_at.is_some());
+
+        db.set_status_down(&url, &Lookup::default(), true)
+            .await
+            .unwrap();
+
+        assert!(db.endpoint.get(&url).await.unwrap().uptime_at.is_none());
+        let incident = db.incident.get_open(&url).await.unwrap().unwrap();
+        assert!(incident.maintenance);
+    }
+}
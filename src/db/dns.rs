@@ -0,0 +1,46 @@
+use chrono::Local;
+
+use super::{run, Connection};
+
+/// Stores the last answer set of each DNS monitor so changes can be detected
+#[derive(Debug)]
+pub struct DnsAnswerModel {
+    pool: Connection,
+}
+
+impl DnsAnswerModel {
+    pub fn new(pool: Connection) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get(&self, url: &str) -> anyhow::Result<Option<Vec<String>>> {
+        let row: Option<String> = run!(
+            self.pool,
+            sqlx::query_scalar("SELECT answers FROM dns_answer WHERE url = $1").bind(url),
+            fetch_optional
+        )?;
+
+        let answers = row.map(|answers| answers.lines().map(|answer| answer.to_string()).collect());
+
+        Ok(answers)
+    }
+
+    pub async fn set(&self, url: &str, answers: &[String]) -> anyhow::Result<()> {
+        let answers = answers.join("\n");
+        let now = Local::now().naive_local();
+
+        run!(
+            self.pool,
+            sqlx::query(
+                "INSERT INTO dns_answer (url, answers, updated_at) VALUES ($1, $2, $3)
+                ON CONFLICT (url) DO UPDATE SET answers = excluded.answers, updated_at = excluded.updated_at"
+            )
+            .bind(url)
+            .bind(&answers)
+            .bind(now),
+            execute
+        )?;
+
+        Ok(())
+    }
+}
@@ -1,160 +1,398 @@
-use chrono::NaiveDateTime;
-use reqwest::StatusCode;
+use chrono::{Local, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
-use super::{url::Url, Connection};
+use super::{heartbeat::HeartbeatModel, run, url::Url, Connection};
+use crate::{
+    check,
+    constants::{get_timeout, get_tries},
+    heartbeat::HeartbeatCheck,
+    probe::{Snapshot, Timings},
+    tls::{self, CertificateInfo},
+};
 
-const DEFAULT_TIMEOUT: u64 = 10;
-
-#[derive(Debug)]
+#[derive(Debug, Serialize, sqlx::FromRow)]
 #[allow(unused)]
 pub struct Endpoint {
     pub id: String,
+    #[sqlx(try_from = "String")]
     pub url: Url,
+    #[sqlx(try_from = "String")]
     pub status: Status,
     pub uptime_at: Option<NaiveDateTime>,
     pub max_latency: Option<i64>,
     pub created_at: NaiveDateTime,
+    /// Consecutive failed checks
+    pub failures: i64,
+    /// Consecutive successful checks
+    pub successes: i64,
+    pub flapping_since: Option<NaiveDateTime>,
+    /// The endpoint this one depends on that was down when it went down
+    pub unreachable_via: Option<String>,
+    /// The options of the URL, see [`Url`]
+    pub options: String,
+    /// `env` for the endpoints from `URLS`, `api` for the ones created
+    /// through the API
+    pub source: String,
+}
+
+impl Endpoint {
+    /// The URL along with its options
+    pub fn configured_url(&self) -> Url {
+        Url::from(format!("{}#{}", self.url, self.options))
+    }
 }
 
 #[derive(Debug)]
 pub struct EndpointModel {
     pool: Connection,
-    client: reqwest::Client,
+    timeout: Duration,
     tries: u8,
+    heartbeat: HeartbeatModel,
 }
 
 impl EndpointModel {
-    pub async fn new(pool: Connection, urls: &Vec<Url>) -> anyhow::Result<Self> {
-        let timeout = std::env::var("TIMEOUT")
-            .unwrap_or(DEFAULT_TIMEOUT.to_string())
-            .parse::<u64>()?;
-
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(timeout))
-            .build()?;
+    pub async fn new(pool: Connection, urls: &[Url]) -> anyhow::Result<Self> {
+        let timeout = get_timeout();
 
         for url in urls.iter() {
+            let options = url.options_string();
             let url = url.as_str();
 
-            let row = sqlx::query!("SELECT COUNT(*) as count FROM endpoint WHERE url = ?", url)
-                .fetch_one(&pool)
-                .await?;
-
-            let exists = row.count > 0;
-
-            if exists {
-                continue;
-            }
-
-            sqlx::query!(
-                "INSERT INTO endpoint (url, status, uptime_at) VALUES (?, 'PENDING', NULL)",
-                url
-            )
-            .execute(&pool)
-            .await?;
+            run!(
+                pool,
+                sqlx::query(
+                    "INSERT INTO endpoint (url, status, uptime_at, options, source)
+                    VALUES ($1, 'PENDING', NULL, $2, 'env')
+                    ON CONFLICT (url) DO UPDATE SET options = excluded.options, source = 'env'"
+                )
+                .bind(url)
+                .bind(&options),
+                execute
+            )?;
         }
 
-        let tries = std::env::var("TRIES")
-            .unwrap_or("2".to_string())
-            .parse::<u8>()?;
-
-        if tries < 1 {
-            panic!("TRIES must be greater than 0");
-        }
+        let tries = get_tries();
+        let heartbeat = HeartbeatModel::new(pool.clone());
 
         Ok(Self {
             pool,
-            client,
+            timeout,
             tries,
+            heartbeat,
         })
     }
 
     pub async fn get_all(&self) -> anyhow::Result<Vec<Endpoint>> {
-        let endpoints = sqlx::query_as!(Endpoint, "SELECT * FROM endpoint")
-            .fetch_all(&self.pool)
-            .await?;
+        let endpoints = run!(
+            self.pool,
+            sqlx::query_as("SELECT * FROM endpoint"),
+            fetch_all
+        )?;
 
         Ok(endpoints)
     }
 
     pub async fn get(&self, url: &str) -> anyhow::Result<Endpoint> {
-        let endpoint = sqlx::query_as!(Endpoint, "SELECT * FROM endpoint WHERE url = ?", url)
-            .fetch_one(&self.pool)
-            .await?;
+        let endpoint = run!(
+            self.pool,
+            sqlx::query_as("SELECT * FROM endpoint WHERE url = $1").bind(url),
+            fetch_one
+        )?;
 
         Ok(endpoint)
     }
 
+    pub async fn get_by_id(&self, id: &str) -> anyhow::Result<Option<Endpoint>> {
+        let endpoint = run!(
+            self.pool,
+            sqlx::query_as("SELECT * FROM endpoint WHERE id = $1").bind(id),
+            fetch_optional
+        )?;
+
+        Ok(endpoint)
+    }
+
+    /// The URLs of the endpoints that were created through the API
+    pub async fn get_api_urls(&self) -> anyhow::Result<Vec<Url>> {
+        let endpoints: Vec<Endpoint> = run!(
+            self.pool,
+            sqlx::query_as("SELECT * FROM endpoint WHERE source = 'api' ORDER BY created_at, url"),
+            fetch_all
+        )?;
+
+        Ok(endpoints
+            .iter()
+            .map(|endpoint| endpoint.configured_url())
+            .collect())
+    }
+
+    pub async fn create(&self, url: &Url) -> anyhow::Result<Endpoint> {
+        let options = url.options_string();
+        let url = url.as_str();
+
+        run!(
+            self.pool,
+            sqlx::query(
+                "INSERT INTO endpoint (url, status, uptime_at, options, source)
+                VALUES ($1, 'PENDING', NULL, $2, 'api')"
+            )
+            .bind(url)
+            .bind(&options),
+            execute
+        )?;
+
+        self.get(url).await
+    }
+
+    pub async fn update_options(&self, url: &Url) -> anyhow::Result<Endpoint> {
+        let options = url.options_string();
+        let url = url.as_str();
+
+        run!(
+            self.pool,
+            sqlx::query("UPDATE endpoint SET options = $1 WHERE url = $2")
+                .bind(&options)
+                .bind(url),
+            execute
+        )?;
+
+        self.get(url).await
+    }
+
+    /// Deletes the endpoint along with its history in one transaction, so
+    /// nothing is left for the digest or for an endpoint created again with
+    /// the same URL
+    pub async fn delete(&self, url: &str) -> anyhow::Result<()> {
+        const QUERIES: [&str; 9] = [
+            "DELETE FROM incident_snapshot WHERE incident_id IN (SELECT id FROM incident WHERE url = $1)",
+            "DELETE FROM incident WHERE url = $1",
+            "DELETE FROM check_result WHERE url = $1",
+            "DELETE FROM status_change WHERE url = $1",
+            "DELETE FROM location_check WHERE url = $1",
+            "DELETE FROM certificate WHERE url = $1",
+            "DELETE FROM dns_answer WHERE url = $1",
+            "DELETE FROM heartbeat WHERE url = $1",
+            "DELETE FROM endpoint WHERE url = $1",
+        ];
+
+        match &self.pool {
+            Connection::Sqlite(pool) => {
+                let mut tx = pool.begin().await?;
+                for query in QUERIES {
+                    sqlx::query(query).bind(url).execute(&mut *tx).await?;
+                }
+                tx.commit().await?;
+            }
+            Connection::Postgres(pool) => {
+                let mut tx = pool.begin().await?;
+                for query in QUERIES {
+                    sqlx::query(query).bind(url).execute(&mut *tx).await?;
+                }
+                tx.commit().await?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub async fn get_max_latency(&self, url: &str) -> anyhow::Result<Option<i64>> {
-        let latency = sqlx::query!("SELECT max_latency FROM endpoint WHERE url = ?", url)
-            .fetch_one(&self.pool)
-            .await?;
+        let latency = run!(
+            self.pool,
+            sqlx::query_scalar("SELECT max_latency FROM endpoint WHERE url = $1").bind(url),
+            fetch_one
+        )?;
 
-        Ok(latency.max_latency)
+        Ok(latency)
     }
 
     pub async fn relative_max_latency_update(&self, url: &str, latency: i64) -> anyhow::Result<()> {
         let max_latency = self.get_max_latency(url).await?.unwrap_or(0);
 
         if latency > max_latency {
-            sqlx::query!(
-                "UPDATE endpoint SET max_latency = ? WHERE url = ?",
-                latency,
-                url
-            )
-            .execute(&self.pool)
-            .await?;
+            run!(
+                self.pool,
+                sqlx::query("UPDATE endpoint SET max_latency = $1 WHERE url = $2")
+                    .bind(latency)
+                    .bind(url),
+                execute
+            )?;
         }
 
         Ok(())
     }
 
     pub async fn reset_max_latency(&self, url: &str) -> anyhow::Result<()> {
-        sqlx::query!("UPDATE endpoint SET max_latency = NULL WHERE url = ?", url)
-            .execute(&self.pool)
-            .await?;
+        run!(
+            self.pool,
+            sqlx::query("UPDATE endpoint SET max_latency = NULL WHERE url = $1").bind(url),
+            execute
+        )?;
 
         Ok(())
     }
 
-    /// Returns `true` if the URL is up
-    /// Returns `false` if down
-    pub async fn lookup(&self, url: &Url) -> anyhow::Result<bool> {
-        for _ in 0..self.tries {
-            let res = self.send_request(url).await?;
+    /// Counts the consecutive successful or failed checks of the URL
+    pub async fn record_check(&self, url: &str, is_success: bool) -> anyhow::Result<()> {
+        let query = if is_success {
+            "UPDATE endpoint SET successes = successes + 1, failures = 0 WHERE url = $1"
+        } else {
+            "UPDATE endpoint SET failures = failures + 1, successes = 0 WHERE url = $1"
+        };
 
-            if res {
-                return Ok(true);
-            }
+        run!(self.pool, sqlx::query(query).bind(url), execute)?;
 
-            tokio::time::sleep(Duration::from_secs(1)).await;
-        }
+        Ok(())
+    }
 
-        Ok(false)
+    pub async fn set_flapping_since(
+        &self,
+        url: &str,
+        flapping_since: Option<NaiveDateTime>,
+    ) -> anyhow::Result<()> {
+        run!(
+            self.pool,
+            sqlx::query("UPDATE endpoint SET flapping_since = $1 WHERE url = $2")
+                .bind(flapping_since)
+                .bind(url),
+            execute
+        )?;
+
+        Ok(())
     }
 
-    async fn send_request(&self, url: &Url) -> anyhow::Result<bool> {
-        let start = std::time::Instant::now();
-        let res = self.client.get(url.as_str()).send().await;
-        let latency = start.elapsed().as_millis() as i64;
+    /// Checks the URL, see [`check::lookup`], heartbeats are checked
+    /// against their last ping instead
+    pub async fn lookup(&self, url: &Url) -> anyhow::Result<Lookup> {
+        // Retrying won't make a ping arrive
+        if url.is_heartbeat() {
+            return self.check_heartbeat(url).await;
+        }
+
+        let lookup = check::lookup(url, self.timeout, self.tries).await?;
 
-        self.relative_max_latency_update(url.as_str(), latency)
-            .await?;
+        if let Some(latency) = lookup.latency {
+            self.relative_max_latency_update(url.as_str(), latency)
+                .await?;
+        }
+
+        Ok(lookup)
+    }
 
-        if let Ok(res) = res {
-            let status = res.status();
+    /// Fetches the certificate of an HTTPS endpoint or TLS service
+    pub async fn certificate(&self, url: &Url) -> anyhow::Result<CertificateInfo> {
+        let (host, port) = url
+            .tls_address()
+            .ok_or_else(|| anyhow::anyhow!("{} doesn't use TLS", url))?;
 
-            if status.is_success() || status == StatusCode::TOO_MANY_REQUESTS {
-                return Ok(true);
+        tls::inspect(&host, port, self.timeout).await
+    }
+
+    /// A heartbeat monitor is up while its last ping succeeded and the next
+    /// one isn't overdue, a monitor that was never pinged is measured from
+    /// its creation
+    async fn check_heartbeat(&self, url: &Url) -> anyhow::Result<Lookup> {
+        let check = HeartbeatCheck::parse(url)?;
+
+        let lookup = match self.heartbeat.get(url).await? {
+            Some(heartbeat) if !heartbeat.success => Lookup {
+                failure: Some(Failure::Heartbeat),
+                detail: heartbeat.message,
+                ..Default::default()
+            },
+            Some(heartbeat) => Lookup {
+                is_success: !check.is_late(heartbeat.pinged_at, Local::now().naive_local()),
+                ..Default::default()
+            },
+            None => {
+                // `created_at` is set by the database in UTC
+                let created_at = self.get(url).await?.created_at;
+
+                Lookup {
+                    is_success: !check.is_late(created_at, Utc::now().naive_utc()),
+                    ..Default::default()
+                }
             }
         };
 
-        Ok(false)
+        if lookup.is_success || lookup.failure.is_some() {
+            return Ok(lookup);
+        }
+
+        Ok(Lookup {
+            failure: Some(Failure::Heartbeat),
+            detail: Some("The ping is overdue".to_string()),
+            ..lookup
+        })
+    }
+}
+
+/// The outcome of checking a URL
+#[derive(Debug, Default)]
+pub struct Lookup {
+    pub is_success: bool,
+    /// Milliseconds the check took, heartbeats don't have one
+    pub latency: Option<i64>,
+    /// The records a DNS monitor resolved
+    pub answers: Option<Vec<String>>,
+    /// Details about why the check failed
+    pub detail: Option<String>,
+    /// The phases of an HTTP check
+    pub timings: Option<Timings>,
+    /// Why the check failed, `None` when it succeeded
+    pub failure: Option<Failure>,
+    /// The response of a failed HTTP check
+    pub snapshot: Option<Snapshot>,
+    /// The locations the check failed from when agents report to the monitor
+    pub failing_locations: Vec<String>,
+}
+
+/// The kind of failure of a check, stored as snake case text
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum Failure {
+    Dns,
+    ConnectionRefused,
+    /// Any other network error
+    Connection,
+    Timeout,
+    Tls,
+    /// An unexpected HTTP status
+    Http,
+    /// The response or the answers didn't match what's expected
+    Assertion,
+    BodyTooLarge,
+    /// A heartbeat was reported as failed or didn't arrive in time
+    Heartbeat,
+}
+
+impl Failure {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Failure::Dns => "DNS failure",
+            Failure::ConnectionRefused => "Connection refused",
+            Failure::Connection => "Connection error",
+            Failure::Timeout => "Timeout",
+            Failure::Tls => "TLS error",
+            Failure::Http => "HTTP error",
+            Failure::Assertion => "Assertion failed",
+            Failure::BodyTooLarge => "Body too large",
+            Failure::Heartbeat => "Heartbeat failed",
+        }
+    }
+
+    /// The label with the error detail, e.g. `Timeout (No response after 10s)`
+    pub fn describe(&self, detail: Option<&str>) -> String {
+        match detail {
+            Some(detail) => format!("{} ({})", self.label(), detail),
+            None => self.label().to_string(),
+        }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
 pub enum Status {
     Up,
     Down,
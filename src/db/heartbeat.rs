@@ -0,0 +1,61 @@
+use chrono::{Local, NaiveDateTime};
+
+use super::{run, Connection};
+
+/// The last ping a heartbeat monitor received
+#[derive(Debug, sqlx::FromRow)]
+#[allow(unused)]
+pub struct Heartbeat {
+    pub url: String,
+    pub success: bool,
+    pub message: Option<String>,
+    pub pinged_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct HeartbeatModel {
+    pool: Connection,
+}
+
+impl HeartbeatModel {
+    pub fn new(pool: Connection) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get(&self, url: &str) -> anyhow::Result<Option<Heartbeat>> {
+        let heartbeat = run!(
+            self.pool,
+            sqlx::query_as("SELECT * FROM heartbeat WHERE url = $1").bind(url),
+            fetch_optional
+        )?;
+
+        Ok(heartbeat)
+    }
+
+    pub async fn ping(
+        &self,
+        url: &str,
+        success: bool,
+        message: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let now = Local::now().naive_local();
+
+        run!(
+            self.pool,
+            sqlx::query(
+                "INSERT INTO heartbeat (url, success, message, pinged_at) VALUES ($1, $2, $3, $4)
+                ON CONFLICT (url) DO UPDATE SET
+                    success = excluded.success,
+                    message = excluded.message,
+                    pinged_at = excluded.pinged_at"
+            )
+            .bind(url)
+            .bind(success)
+            .bind(message)
+            .bind(now),
+            execute
+        )?;
+
+        Ok(())
+    }
+}
@@ -1,41 +1,105 @@
-use sqlx::SqlitePool;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
+    PgPool,
+};
+use std::{str::FromStr, time::Duration};
+use tracing::{debug, info, warn};
 
 use super::Connection;
+use crate::{DEFAULT_DATABASE_URL, SQLITE_BUSY_TIMEOUT};
 
-pub async fn connect(verbose: bool) -> anyhow::Result<Connection> {
-    let db_url = "sqlite:db/db.sqlite";
+/// Connects to PostgreSQL for a `postgres://` URL and to SQLite otherwise
+pub async fn connect(db_url: &str) -> anyhow::Result<Connection> {
+    if db_url.starts_with("postgres://") || db_url.starts_with("postgresql://") {
+        let pool = PgPool::connect(db_url).await?;
 
-    let pool = SqlitePool::connect(&db_url).await?;
+        debug!("Connected to PostgreSQL");
 
-    if verbose {
-        println!("Connected to the database {}", &db_url);
+        return Ok(Connection::Postgres(pool));
     }
 
-    Ok(pool)
-}
+    let options = SqliteConnectOptions::from_str(db_url)?
+        .create_if_missing(true)
+        .busy_timeout(Duration::from_millis(SQLITE_BUSY_TIMEOUT));
+
+    // An in-memory database lives as long as one of its connections
+    if db_url.contains(":memory:") || db_url.contains("mode=memory") {
+        let pool = SqlitePoolOptions::new()
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await?;
 
-pub fn create_db_if_not_exists() -> anyhow::Result<()> {
-    let exists = std::path::Path::new("db/db.sqlite").exists();
-    if !exists {
-        println!("Creating the database...");
+        debug!("Connected to an in-memory database");
 
-        std::fs::create_dir_all("db")?;
-        std::fs::write("db/db.sqlite", "")?;
+        return Ok(Connection::Sqlite(pool));
     }
 
-    Ok(())
+    // SQLite creates the file but not its directory
+    let path = options.clone().get_filename().into_owned();
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        if !dir.exists() {
+            info!(path = %dir.display(), "Creating the database directory");
+            std::fs::create_dir_all(dir)?;
+        }
+    }
+
+    // The checks write while the API and the status page read
+    let options = options
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal);
+    let pool = SqlitePoolOptions::new().connect_with(options).await?;
+
+    debug!(path = %path.display(), "Connected to the database");
+
+    Ok(Connection::Sqlite(pool))
 }
 
-pub async fn migrate(pool: &Connection, verbose: bool) -> anyhow::Result<()> {
-    if verbose {
-        println!("Running the migrations...");
+/// Versions before `DATABASE_URL` was read at runtime always used the
+/// default file, and the `.env` of their setup pointed `DATABASE_URL` to
+/// another one for `sqlx-cli`. Warns when that data would be left behind
+pub fn warn_about_previous_database(db_url: &str) {
+    if !db_url.starts_with("sqlite:") || db_url.contains(":memory:") {
+        return;
     }
 
-    sqlx::migrate!("./migrations/").run(pool).await?;
+    let filename = |url: &str| {
+        SqliteConnectOptions::from_str(url)
+            .ok()
+            .map(|options| options.get_filename().into_owned())
+    };
+    let (Some(path), Some(previous)) = (filename(db_url), filename(DEFAULT_DATABASE_URL)) else {
+        return;
+    };
 
-    if verbose {
-        println!("Migrations completed!");
+    // Nothing is left behind when the previous file is the configured one or
+    // doesn't exist
+    let Ok(previous) = previous.canonicalize() else {
+        return;
+    };
+    if path.canonicalize().is_ok_and(|path| path == previous) {
+        return;
     }
 
+    warn!(
+        database = %path.display(),
+        previous = %previous.display(),
+        "DATABASE_URL isn't the database of the previous versions, unset it or move the data \
+        to keep the history"
+    );
+}
+
+/// Each backend has its own migrations with the same schema
+pub async fn migrate(pool: &Connection) -> anyhow::Result<()> {
+    debug!("Running the migrations");
+
+    match pool {
+        Connection::Sqlite(pool) => sqlx::migrate!("./migrations/sqlite/").run(pool).await?,
+        Connection::Postgres(pool) => sqlx::migrate!("./migrations/postgres/").run(pool).await?,
+    }
+
+    debug!("Migrations completed");
+
     Ok(())
 }
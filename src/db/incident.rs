@@ -1,15 +1,35 @@
-use chrono::NaiveDateTime;
-use sqlx::sqlite::SqliteQueryResult;
+use super::{endpoint::Failure, run, Connection};
+use chrono::{Local, NaiveDateTime};
+use serde::Serialize;
 
-use super::Connection;
-
-#[derive(Debug)]
+#[derive(Debug, Serialize, sqlx::FromRow)]
 #[allow(unused)]
 pub struct Incident {
     pub id: String,
     pub url: String,
     pub message: String,
     pub created_at: NaiveDateTime,
+    /// Happened during a maintenance window, doesn't count as downtime
+    pub maintenance: bool,
+    /// Included in a digest already
+    pub reported: bool,
+    /// When the endpoint went up again, `None` while it's still down
+    pub resolved_at: Option<NaiveDateTime>,
+    /// Why the check that opened the incident failed
+    pub failure: Option<Failure>,
+    pub failure_detail: Option<String>,
+}
+
+/// Narrows down the incidents returned by [`IncidentModel::find`]
+#[derive(Debug, Default)]
+pub struct IncidentFilter {
+    pub url: Option<String>,
+    /// Only the incidents of these URLs, applied before the limit
+    pub urls: Option<Vec<String>>,
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+    pub maintenance: Option<bool>,
+    pub limit: Option<i64>,
 }
 
 #[derive(Debug)]
@@ -22,36 +42,193 @@ impl IncidentModel {
         Self { pool }
     }
 
-    pub async fn get_all(&self) -> anyhow::Result<Vec<Incident>> {
-        let incidents = sqlx::query_as!(Incident, "SELECT * FROM incident")
-            .fetch_all(&self.pool)
-            .await?;
+    /// The incidents that haven't been included in a digest yet
+    pub async fn get_unreported(&self) -> anyhow::Result<Vec<Incident>> {
+        let incidents = run!(
+            self.pool,
+            sqlx::query_as("SELECT * FROM incident WHERE reported = FALSE ORDER BY created_at"),
+            fetch_all
+        )?;
 
         Ok(incidents)
     }
 
-    pub async fn is_empty(&self) -> anyhow::Result<bool> {
-        let incidents = sqlx::query!("SELECT id FROM incident")
-            .fetch_all(&self.pool)
-            .await?;
+    /// The incidents matching the filter, newest first
+    pub async fn find(&self, filter: &IncidentFilter) -> anyhow::Result<Vec<Incident>> {
+        let urls = filter.urls.as_deref().unwrap_or_default();
+        if filter.urls.is_some() && urls.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // The URLs are bound after the fixed parameters
+        let urls_condition = if urls.is_empty() {
+            String::new()
+        } else {
+            let placeholders = (6..6 + urls.len())
+                .map(|i| format!("${}", i))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("AND url IN ({})", placeholders)
+        };
+
+        // A condition is skipped when its parameter is NULL
+        let query = format!(
+            "SELECT * FROM incident
+            WHERE ($1 IS NULL OR url = $1)
+                AND ($2 IS NULL OR created_at >= $2)
+                AND ($3 IS NULL OR created_at < $3)
+                AND ($4 IS NULL OR maintenance = $4)
+                {}
+            ORDER BY created_at DESC
+            LIMIT $5",
+            urls_condition
+        );
+
+        let incidents = run!(
+            self.pool,
+            urls.iter().fold(
+                sqlx::query_as(&query)
+                    .bind(&filter.url)
+                    .bind(filter.since)
+                    .bind(filter.until)
+                    .bind(filter.maintenance)
+                    .bind(filter.limit.unwrap_or(i64::MAX)),
+                |query, url| query.bind(url)
+            ),
+            fetch_all
+        )?;
 
-        Ok(incidents.is_empty())
+        Ok(incidents)
     }
 
-    pub async fn delete_many(&self, ids: Vec<&str>) -> anyhow::Result<SqliteQueryResult> {
+    /// Marks the incidents as included in a digest, they're kept for the
+    /// history
+    pub async fn mark_reported(&self, ids: Vec<&str>) -> anyhow::Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let placeholders = (1..=ids.len())
+            .map(|i| format!("${}", i))
+            .collect::<Vec<_>>()
+            .join(", ");
         let query = format!(
-            "DELETE FROM incident WHERE id IN ({})",
-            ids.iter().map(|_| "?").collect::<Vec<_>>().join(", ")
+            "UPDATE incident SET reported = TRUE WHERE id IN ({})",
+            placeholders
         );
 
-        let mut q = sqlx::query(&query);
+        run!(
+            self.pool,
+            ids.iter()
+                .fold(sqlx::query(&query), |query, id| query.bind(*id)),
+            execute
+        )?;
+
+        Ok(())
+    }
+
+    /// The latest incident of the URL that isn't resolved yet
+    pub async fn get_open(&self, url: &str) -> anyhow::Result<Option<Incident>> {
+        let incident = run!(
+            self.pool,
+            sqlx::query_as(
+                "SELECT * FROM incident WHERE url = $1 AND resolved_at IS NULL
+                ORDER BY created_at DESC LIMIT 1"
+            )
+            .bind(url),
+            fetch_optional
+        )?;
+
+        Ok(incident)
+    }
+
+    /// Replaces the message and the cause of the incident
+    pub async fn set_cause(
+        &self,
+        id: &str,
+        message: &str,
+        failure: Option<Failure>,
+        failure_detail: Option<&str>,
+    ) -> anyhow::Result<()> {
+        run!(
+            self.pool,
+            sqlx::query(
+                "UPDATE incident SET message = $1, failure = $2, failure_detail = $3 WHERE id = $4"
+            )
+            .bind(message)
+            .bind(failure)
+            .bind(failure_detail)
+            .bind(id),
+            execute
+        )?;
+
+        Ok(())
+    }
+
+    /// Resolves the open incidents of the URL
+    pub async fn resolve(&self, url: &str) -> anyhow::Result<()> {
+        let now = Local::now().naive_local();
+
+        run!(
+            self.pool,
+            sqlx::query(
+                "UPDATE incident SET resolved_at = $1 WHERE url = $2 AND resolved_at IS NULL"
+            )
+            .bind(now)
+            .bind(url),
+            execute
+        )?;
+
+        Ok(())
+    }
+
+    /// Deletes the incidents that are older than `before`
+    pub async fn delete_before(&self, before: NaiveDateTime) -> anyhow::Result<()> {
+        run!(
+            self.pool,
+            sqlx::query("DELETE FROM incident WHERE created_at < $1").bind(before),
+            execute
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{endpoint::Lookup, url::Url, Db};
+
+    #[tokio::test]
+    async fn test_find_filters_urls_before_the_limit() {
+        let public = Url::from("https://example.com#name=example&public=true".to_string());
+        let private = Url::from("https://internal.example.com#name=internal".to_string());
+        let db = Db::in_memory(&[public.clone(), private.clone()])
+            .await
+            .unwrap();
 
-        for id in ids {
-            q = q.bind(id);
+        db.set_status_down(&public, &Lookup::default(), false)
+            .await
+            .unwrap();
+        for _ in 0..5 {
+            db.set_status_down(&private, &Lookup::default(), false)
+                .await
+                .unwrap();
         }
 
-        let result = q.execute(&self.pool).await?;
+        let filter = IncidentFilter {
+            urls: Some(vec![public.as_str().to_string()]),
+            limit: Some(3),
+            ..Default::default()
+        };
+        let incidents = db.incident.find(&filter).await.unwrap();
+        assert_eq!(incidents.len(), 1);
+        assert_eq!(incidents[0].url, public.as_str());
 
-        Ok(result)
+        let filter = IncidentFilter {
+            urls: Some(Vec::new()),
+            ..Default::default()
+        };
+        assert!(db.incident.find(&filter).await.unwrap().is_empty());
     }
 }
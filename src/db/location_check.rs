@@ -0,0 +1,89 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use super::{endpoint::Failure, run, Connection};
+use crate::health::HEALTH;
+
+/// The latest check of an endpoint from one location
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct LocationCheck {
+    #[serde(skip)]
+    pub url: String,
+    pub location: String,
+    pub success: bool,
+    pub latency: Option<i64>,
+    pub failure: Option<Failure>,
+    pub failure_detail: Option<String>,
+    /// When the monitor received it, the clocks of the agents aren't used
+    pub checked_at: NaiveDateTime,
+}
+
+#[derive(Debug)]
+pub struct LocationCheckModel {
+    pool: Connection,
+}
+
+impl LocationCheckModel {
+    pub fn new(pool: Connection) -> Self {
+        Self { pool }
+    }
+
+    /// Replaces the previous check of the location
+    pub async fn set(&self, check: &LocationCheck) -> anyhow::Result<()> {
+        run!(
+            self.pool,
+            sqlx::query(
+                "INSERT INTO location_check (url, location, success, latency, failure, failure_detail, checked_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (url, location) DO UPDATE SET
+                    success = excluded.success,
+                    latency = excluded.latency,
+                    failure = excluded.failure,
+                    failure_detail = excluded.failure_detail,
+                    checked_at = excluded.checked_at"
+            )
+            .bind(&check.url)
+            .bind(&check.location)
+            .bind(check.success)
+            .bind(check.latency)
+            .bind(check.failure)
+            .bind(&check.failure_detail)
+            .bind(check.checked_at),
+            execute
+        )?;
+        HEALTH.record_db_write();
+
+        Ok(())
+    }
+
+    /// The checks of the URL received since `since`, by location
+    pub async fn get_since(
+        &self,
+        url: &str,
+        since: NaiveDateTime,
+    ) -> anyhow::Result<Vec<LocationCheck>> {
+        let checks = run!(
+            self.pool,
+            sqlx::query_as(
+                "SELECT * FROM location_check
+                WHERE url = $1 AND checked_at >= $2 ORDER BY location"
+            )
+            .bind(url)
+            .bind(since),
+            fetch_all
+        )?;
+
+        Ok(checks)
+    }
+
+    pub async fn get_all(&self, url: &str) -> anyhow::Result<Vec<LocationCheck>> {
+        let checks = run!(
+            self.pool,
+            sqlx::query_as("SELECT * FROM location_check WHERE url = $1 ORDER BY location")
+                .bind(url),
+            fetch_all
+        )?;
+
+        Ok(checks)
+    }
+}
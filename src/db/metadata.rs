@@ -1,37 +1,51 @@
-use crate::{db::Connection, UPDATE_INTERVAL};
-use chrono::{Local, NaiveDateTime};
+use crate::{
+    db::{run, Connection},
+    UPDATE_INTERVAL,
+};
+use chrono::{Local, NaiveDateTime, Utc};
+use std::time::Duration;
 
 #[derive(Debug)]
 pub struct MetadataModel {
     pool: Connection,
 }
 
-#[derive(Debug)]
+#[derive(Debug, sqlx::FromRow)]
 #[allow(unused)]
 pub struct Metadata {
     pub id: i64,
     pub last_update_sent_at: Option<NaiveDateTime>,
+    /// The instance holding the leader lease
+    pub leader: Option<String>,
+    /// In UTC so instances in different time zones agree on it
+    pub leader_until: Option<NaiveDateTime>,
 }
 
 impl MetadataModel {
     pub async fn new(pool: Connection) -> anyhow::Result<Self> {
-        let metadata = sqlx::query_as!(Metadata, "SELECT * FROM metadata;")
-            .fetch_optional(&pool)
-            .await?;
+        let metadata: Option<Metadata> = run!(
+            pool,
+            sqlx::query_as("SELECT * FROM metadata;"),
+            fetch_optional
+        )?;
 
         if metadata.is_none() {
-            sqlx::query!("INSERT INTO metadata (last_update_sent_at) VALUES (NULL);")
-                .execute(&pool)
-                .await?;
+            run!(
+                pool,
+                sqlx::query("INSERT INTO metadata (last_update_sent_at) VALUES (NULL);"),
+                execute
+            )?;
         }
 
         Ok(Self { pool })
     }
 
     pub async fn get(&self) -> anyhow::Result<Metadata> {
-        let metadata = sqlx::query_as!(Metadata, "SELECT * FROM metadata;")
-            .fetch_one(&self.pool)
-            .await?;
+        let metadata = run!(
+            self.pool,
+            sqlx::query_as("SELECT * FROM metadata;"),
+            fetch_one
+        )?;
 
         Ok(metadata)
     }
@@ -39,9 +53,80 @@ impl MetadataModel {
     pub async fn update_last_sent_at(&self) -> anyhow::Result<()> {
         let now = Local::now().naive_local();
 
-        sqlx::query!("UPDATE metadata SET last_update_sent_at = ?;", now)
-            .execute(&self.pool)
-            .await?;
+        run!(
+            self.pool,
+            sqlx::query("UPDATE metadata SET last_update_sent_at = $1;").bind(now),
+            execute
+        )?;
+
+        Ok(())
+    }
+
+    /// Marks the instance as running, returns `true` if its previous run
+    /// didn't shut down cleanly
+    pub async fn start(&self, instance: &str) -> anyhow::Result<bool> {
+        let was_running: Option<bool> = run!(
+            self.pool,
+            sqlx::query_scalar("SELECT running FROM instance WHERE id = $1;").bind(instance),
+            fetch_optional
+        )?;
+
+        run!(
+            self.pool,
+            sqlx::query(
+                "INSERT INTO instance (id, running) VALUES ($1, TRUE)
+                ON CONFLICT (id) DO UPDATE SET running = TRUE;"
+            )
+            .bind(instance),
+            execute
+        )?;
+
+        Ok(was_running.unwrap_or(false))
+    }
+
+    /// Records a clean shutdown of the instance
+    pub async fn stop(&self, instance: &str) -> anyhow::Result<()> {
+        run!(
+            self.pool,
+            sqlx::query("UPDATE instance SET running = FALSE WHERE id = $1;").bind(instance),
+            execute
+        )?;
+
+        Ok(())
+    }
+
+    /// Takes the leader lease if it's free or expired, or renews it when
+    /// `instance` already holds it. Returns `true` if `instance` is the leader
+    pub async fn acquire_lease(&self, instance: &str, lease: Duration) -> anyhow::Result<bool> {
+        let now = Utc::now().naive_utc();
+        let until = now + chrono::Duration::from_std(lease)?;
+
+        let updated = run!(
+            self.pool,
+            sqlx::query(
+                "UPDATE metadata SET leader = $1, leader_until = $2
+                WHERE leader IS NULL OR leader = $1 OR leader_until < $3;"
+            )
+            .bind(instance)
+            .bind(until)
+            .bind(now),
+            execute
+        )?;
+
+        Ok(updated > 0)
+    }
+
+    /// Frees the lease so the standby takes over without waiting for it to
+    /// expire
+    pub async fn release_lease(&self, instance: &str) -> anyhow::Result<()> {
+        run!(
+            self.pool,
+            sqlx::query(
+                "UPDATE metadata SET leader = NULL, leader_until = NULL WHERE leader = $1;"
+            )
+            .bind(instance),
+            execute
+        )?;
 
         Ok(())
     }
@@ -64,3 +149,43 @@ impl MetadataModel {
         Ok(interval)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::db::Db;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_lease_is_held_until_released_or_expired() {
+        let db = Db::in_memory(&[]).await.unwrap();
+        let lease = Duration::from_secs(60);
+
+        assert!(db.metadata.acquire_lease("a", lease).await.unwrap());
+        assert!(db.metadata.acquire_lease("a", lease).await.unwrap());
+        assert!(!db.metadata.acquire_lease("b", lease).await.unwrap());
+
+        db.metadata.release_lease("a").await.unwrap();
+        assert!(db
+            .metadata
+            .acquire_lease("b", Duration::ZERO)
+            .await
+            .unwrap());
+
+        // Expired right away
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(db.metadata.acquire_lease("a", lease).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_running_is_tracked_by_instance() {
+        let db = Db::in_memory(&[]).await.unwrap();
+
+        assert!(!db.metadata.start("a").await.unwrap());
+        assert!(!db.metadata.start("b").await.unwrap());
+
+        // `b` stopped cleanly while `a` crashed
+        db.metadata.stop("b").await.unwrap();
+        assert!(db.metadata.start("a").await.unwrap());
+        assert!(!db.metadata.start("b").await.unwrap());
+    }
+}
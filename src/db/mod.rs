@@ -1,8 +1,46 @@
+pub mod certificate;
+pub mod check_result;
+#[allow(clippy::module_inception)]
 pub mod db;
+pub mod dns;
 pub mod endpoint;
+pub mod heartbeat;
 pub mod helpers;
 pub mod incident;
+pub mod location_check;
 pub mod metadata;
+pub mod snapshot;
+pub mod status_change;
 pub mod url;
 
+#[cfg(test)]
+mod tests;
+
 pub use db::*;
+
+/// Runs a query built with `sqlx::query*` on the pool of either backend. The
+/// SQL is written once with `$1` placeholders, which SQLite understands too,
+/// and the query is built again for each backend. `execute` returns the
+/// number of affected rows
+macro_rules! run {
+    ($pool:expr, $query:expr, execute) => {
+        match &$pool {
+            $crate::db::Connection::Sqlite(pool) => $query
+                .execute(pool)
+                .await
+                .map(|result| result.rows_affected()),
+            $crate::db::Connection::Postgres(pool) => $query
+                .execute(pool)
+                .await
+                .map(|result| result.rows_affected()),
+        }
+    };
+    ($pool:expr, $query:expr, $fetch:ident) => {
+        match &$pool {
+            $crate::db::Connection::Sqlite(pool) => $query.$fetch(pool).await,
+            $crate::db::Connection::Postgres(pool) => $query.$fetch(pool).await,
+        }
+    };
+}
+
+pub(crate) use run;
@@ -0,0 +1,48 @@
+use super::{run, Connection};
+use crate::probe::Snapshot;
+
+/// Stores the failing response of each incident for post-mortems, they're
+/// deleted along with their incident
+#[derive(Debug)]
+pub struct SnapshotModel {
+    pool: Connection,
+}
+
+impl SnapshotModel {
+    pub fn new(pool: Connection) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get(&self, incident_id: &str) -> anyhow::Result<Option<Snapshot>> {
+        let snapshot = run!(
+            self.pool,
+            sqlx::query_as(
+                "SELECT remote_ip, status_line, headers, body, truncated
+                FROM incident_snapshot WHERE incident_id = $1"
+            )
+            .bind(incident_id),
+            fetch_optional
+        )?;
+
+        Ok(snapshot)
+    }
+
+    pub async fn add(&self, incident_id: &str, snapshot: &Snapshot) -> anyhow::Result<()> {
+        run!(
+            self.pool,
+            sqlx::query(
+                "INSERT INTO incident_snapshot (incident_id, remote_ip, status_line, headers, body, truncated)
+                VALUES ($1, $2, $3, $4, $5, $6)"
+            )
+            .bind(incident_id)
+            .bind(&snapshot.remote_ip)
+            .bind(&snapshot.status_line)
+            .bind(&snapshot.headers)
+            .bind(&snapshot.body)
+            .bind(snapshot.truncated),
+            execute
+        )?;
+
+        Ok(())
+    }
+}
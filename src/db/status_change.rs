@@ -0,0 +1,45 @@
+use chrono::{Local, NaiveDateTime};
+
+use super::{endpoint::Status, run, Connection};
+
+/// Keeps track of every status change to detect flapping endpoints
+#[derive(Debug)]
+pub struct StatusChangeModel {
+    pool: Connection,
+}
+
+impl StatusChangeModel {
+    pub fn new(pool: Connection) -> Self {
+        Self { pool }
+    }
+
+    pub async fn add(&self, url: &str, status: Status) -> anyhow::Result<()> {
+        let status = String::from(status);
+        let now = Local::now().naive_local();
+
+        run!(
+            self.pool,
+            sqlx::query("INSERT INTO status_change (url, status, created_at) VALUES ($1, $2, $3)")
+                .bind(url)
+                .bind(&status)
+                .bind(now),
+            execute
+        )?;
+
+        Ok(())
+    }
+
+    pub async fn count_since(&self, url: &str, since: NaiveDateTime) -> anyhow::Result<i64> {
+        let count = run!(
+            self.pool,
+            sqlx::query_scalar(
+                "SELECT COUNT(*) FROM status_change WHERE url = $1 AND created_at >= $2"
+            )
+            .bind(url)
+            .bind(since),
+            fetch_one
+        )?;
+
+        Ok(count)
+    }
+}
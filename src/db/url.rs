@@ -1,29 +1,125 @@
-use std::{fmt::Display, ops::Deref};
+use serde::{Serialize, Serializer};
+use std::{collections::BTreeMap, fmt::Display, ops::Deref};
 
+/// A monitored URL, options can be appended after a `#`, e.g.
+/// `https://api.example.com/health#name=api&tags=prod;web`
+///
+/// The options aren't part of the URL itself so they can be changed without
+/// losing the endpoint's history.
 #[derive(Debug, Clone)]
-pub struct Url(String);
+pub struct Url {
+    url: String,
+    options: BTreeMap<String, String>,
+}
 
 impl Url {
     pub fn as_str(&self) -> &str {
-        &self.0
+        &self.url
+    }
+
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options.get(key).map(|value| value.as_str())
+    }
+
+    /// The options as they're written after the `#`
+    pub fn options_string(&self) -> String {
+        self.options
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// The `name` option, defaults to the URL without its scheme
+    pub fn name(&self) -> &str {
+        self.option("name").unwrap_or_else(|| self.strip_prefix())
+    }
+
+    /// The `;` separated `tags` option
+    pub fn tags(&self) -> Vec<&str> {
+        self.option("tags")
+            .map(|tags| tags.split(';').filter(|tag| !tag.is_empty()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Listed on the status page with `public=true`
+    pub fn is_public(&self) -> bool {
+        self.option("public") == Some("true")
+    }
+
+    /// The `group` option, used to group the endpoints on the status page
+    pub fn group(&self) -> Option<&str> {
+        self.option("group")
     }
 
     pub fn strip_prefix(&self) -> &str {
         let url = self.as_str();
 
-        if url.starts_with("http://") {
-            &url[7..]
-        } else if url.starts_with("https://") {
-            &url[8..]
-        } else {
-            url
+        url.strip_prefix("http://")
+            .or_else(|| url.strip_prefix("https://"))
+            .or_else(|| url.strip_prefix("tls://"))
+            .or_else(|| url.strip_prefix("dns://"))
+            .or_else(|| url.strip_prefix("heartbeat://"))
+            .unwrap_or(url)
+    }
+
+    /// Returns `true` for plain TLS services (`tls://host:port`) that are
+    /// checked with a handshake instead of an HTTP request
+    pub fn is_tls_service(&self) -> bool {
+        self.starts_with("tls://")
+    }
+
+    /// Returns `true` for DNS monitors (`dns://name?type=A`)
+    pub fn is_dns(&self) -> bool {
+        self.starts_with("dns://")
+    }
+
+    /// Returns `true` for passive monitors that are pinged by the job they
+    /// monitor (`heartbeat://name?period=86400`)
+    pub fn is_heartbeat(&self) -> bool {
+        self.starts_with("heartbeat://")
+    }
+
+    /// Returns the host and port to open a TLS connection to, `None` if the
+    /// URL doesn't use TLS
+    pub fn tls_address(&self) -> Option<(String, u16)> {
+        let url = reqwest::Url::parse(self.as_str()).ok()?;
+
+        match url.scheme() {
+            "https" | "tls" => {
+                let host = url.host_str()?.to_string();
+                let port = url.port_or_known_default().unwrap_or(443);
+
+                Some((host, port))
+            }
+            _ => None,
         }
     }
 }
 
 impl From<String> for Url {
     fn from(s: String) -> Self {
-        Self(s)
+        let (url, options) = s.trim().split_once('#').unwrap_or((s.trim(), ""));
+
+        let options = options
+            .split('&')
+            .filter(|option| !option.is_empty())
+            .map(|option| {
+                let (key, value) = option.split_once('=').unwrap_or((option, ""));
+                (key.to_string(), value.to_string())
+            })
+            .collect();
+
+        Self {
+            url: url.to_string(),
+            options,
+        }
+    }
+}
+
+impl Serialize for Url {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
     }
 }
 
@@ -31,12 +127,32 @@ impl Deref for Url {
     type Target = str;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.url
     }
 }
 
 impl Display for Url {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options() {
+        let url = Url::from(" https://api.example.com/health#name=api&tags=prod;web".to_string());
+
+        assert_eq!(url.as_str(), "https://api.example.com/health");
+        assert_eq!(url.name(), "api");
+        assert_eq!(url.tags(), vec!["prod", "web"]);
+        assert_eq!(url.options_string(), "name=api&tags=prod;web");
+
+        let url = Url::from("https://example.com".to_string());
+
+        assert_eq!(url.name(), "example.com");
+        assert!(url.tags().is_empty());
     }
 }
@@ -0,0 +1,106 @@
+use anyhow::anyhow;
+
+use crate::db::url::Url;
+
+/// The endpoints the URL depends on through its `;` separated `depends`
+/// option, e.g. `https://api.example.com#name=api&depends=gateway`
+pub fn parents<'a>(url: &Url, urls: &'a [Url]) -> Vec<&'a Url> {
+    let Some(depends) = url.option("depends") else {
+        return Vec::new();
+    };
+
+    depends
+        .split(';')
+        .filter_map(|name| urls.iter().find(|url| url.name() == name))
+        .collect()
+}
+
+/// The endpoints that depend on the URL directly
+pub fn dependents<'a>(url: &Url, urls: &'a [Url]) -> Vec<&'a Url> {
+    urls.iter()
+        .filter(|other| {
+            parents(other, urls)
+                .iter()
+                .any(|parent| parent.as_str() == url.as_str())
+        })
+        .collect()
+}
+
+/// Makes sure every dependency exists and that there are no cycles
+pub fn validate(urls: &[Url]) -> anyhow::Result<()> {
+    for url in urls.iter() {
+        let depends = url.option("depends").unwrap_or_default();
+
+        for name in depends.split(';').filter(|name| !name.is_empty()) {
+            if !urls.iter().any(|url| url.name() == name) {
+                return Err(anyhow!(
+                    "{} depends on unknown endpoint `{}`",
+                    url.name(),
+                    name
+                ));
+            }
+        }
+
+        // Walk up the parents, getting back to the URL means there's a cycle
+        let mut stack = parents(url, urls);
+        let mut visited = Vec::new();
+        while let Some(parent) = stack.pop() {
+            if parent.as_str() == url.as_str() {
+                return Err(anyhow!("{} depends on itself", url.name()));
+            }
+
+            if !visited.contains(&parent.as_str()) {
+                visited.push(parent.as_str());
+                stack.extend(parents(parent, urls));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(items: &[&str]) -> Vec<Url> {
+        items
+            .iter()
+            .map(|item| Url::from(item.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let urls = urls(&[
+            "https://lb.example.com#name=lb",
+            "https://gw.example.com#name=gateway&depends=lb",
+            "https://api.example.com#name=api&depends=gateway",
+            "https://web.example.com#name=web&depends=gateway;api",
+        ]);
+
+        assert!(validate(&urls).is_ok());
+
+        let names = |urls: Vec<&Url>| {
+            urls.iter()
+                .map(|url| url.name().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(parents(&urls[3], &urls)), vec!["gateway", "api"]);
+        assert_eq!(names(dependents(&urls[1], &urls)), vec!["api", "web"]);
+        assert!(dependents(&urls[3], &urls).is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        let cycle = urls(&[
+            "https://a.example.com#name=a&depends=c",
+            "https://b.example.com#name=b&depends=a",
+            "https://c.example.com#name=c&depends=b",
+        ]);
+        assert!(validate(&cycle).is_err());
+
+        let unknown = urls(&["https://a.example.com#name=a&depends=b"]);
+        assert!(validate(&unknown).is_err());
+    }
+}
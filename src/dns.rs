@@ -0,0 +1,252 @@
+use anyhow::anyhow;
+use hickory_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    error::ResolveErrorKind,
+    proto::rr::{RData, RecordType},
+    TokioAsyncResolver,
+};
+use std::{net::SocketAddr, str::FromStr, time::Duration};
+
+use crate::db::url::Url;
+
+/// A DNS monitor parsed from a `dns://` URL, e.g.
+/// `dns://example.com?type=A&expect=93.184.215.14&resolver=1.1.1.1:53`
+///
+/// Without any `expect` values the check only asserts that the answer isn't
+/// empty.
+#[derive(Debug, PartialEq)]
+pub struct DnsCheck {
+    pub name: String,
+    pub record_type: RecordType,
+    pub expect: Vec<String>,
+    pub resolver: Option<SocketAddr>,
+}
+
+impl DnsCheck {
+    pub fn parse(url: &Url) -> anyhow::Result<Self> {
+        let parsed = reqwest::Url::parse(url.as_str())?;
+        let name = parsed
+            .host_str()
+            .ok_or_else(|| anyhow!("{} is missing the name to resolve", url))?
+            .to_lowercase();
+
+        let mut record_type = RecordType::A;
+        let mut expect = Vec::new();
+        let mut resolver = None;
+
+        for (key, value) in parsed.query_pairs() {
+            match key.as_ref() {
+                "type" => record_type = RecordType::from_str(&value.to_uppercase())?,
+                "expect" => expect.push(normalize(&value)),
+                "resolver" => resolver = Some(parse_resolver(&value)?),
+                _ => return Err(anyhow!("Unknown DNS option `{}` in {}", key, url)),
+            }
+        }
+
+        if !matches!(
+            record_type,
+            RecordType::A | RecordType::AAAA | RecordType::CNAME | RecordType::MX | RecordType::TXT
+        ) {
+            return Err(anyhow!("Unsupported DNS record type {}", record_type));
+        }
+
+        Ok(Self {
+            name,
+            record_type,
+            expect,
+            resolver,
+        })
+    }
+
+    /// Returns `true` if every expected value is in the answers, or if the
+    /// answers aren't empty when nothing is expected
+    pub fn matches(&self, answers: &[String]) -> bool {
+        if self.expect.is_empty() {
+            return !answers.is_empty();
+        }
+
+        self.expect.iter().all(|value| answers.contains(value))
+    }
+}
+
+/// Resolves the records of the check and returns them sorted, an empty
+/// list means the name has no records of that type
+pub async fn resolve(check: &DnsCheck, timeout: Duration) -> anyhow::Result<Vec<String>> {
+    let resolver = match check.resolver.or(default_resolver()?) {
+        Some(addr) => {
+            let name_servers =
+                NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true);
+            let config = ResolverConfig::from_parts(None, vec![], name_servers);
+            TokioAsyncResolver::tokio(config, resolver_opts(timeout))
+        }
+        None => {
+            let (config, _) = hickory_resolver::system_conf::read_system_conf()?;
+            TokioAsyncResolver::tokio(config, resolver_opts(timeout))
+        }
+    };
+
+    let name = if check.name.ends_with('.') {
+        check.name.clone()
+    } else {
+        format!("{}.", check.name)
+    };
+
+    let lookup = match resolver.lookup(name, check.record_type).await {
+        Ok(lookup) => lookup,
+        Err(e) => match e.kind() {
+            ResolveErrorKind::NoRecordsFound { .. } => return Ok(Vec::new()),
+            _ => return Err(e.into()),
+        },
+    };
+
+    let mut answers = lookup
+        .record_iter()
+        .filter(|record| record.record_type() == check.record_type)
+        .filter_map(|record| record.data().map(format_rdata))
+        .collect::<Vec<_>>();
+
+    answers.sort();
+    answers.dedup();
+
+    Ok(answers)
+}
+
+fn resolver_opts(timeout: Duration) -> ResolverOpts {
+    let mut opts = ResolverOpts::default();
+    opts.timeout = timeout;
+    opts.attempts = 1;
+    // Every check should hit the resolver
+    opts.cache_size = 0;
+    opts
+}
+
+/// The resolver from the `DNS_RESOLVER` env variable, the system resolver
+/// is used when it's not set
+fn default_resolver() -> anyhow::Result<Option<SocketAddr>> {
+    match std::env::var("DNS_RESOLVER") {
+        Ok(value) => Ok(Some(parse_resolver(&value)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Parses `ip` or `ip:port`, the port defaults to 53
+fn parse_resolver(value: &str) -> anyhow::Result<SocketAddr> {
+    if let Ok(addr) = value.parse() {
+        return Ok(addr);
+    }
+
+    let ip = value
+        .parse()
+        .map_err(|_| anyhow!("Invalid DNS resolver `{}`", value))?;
+
+    Ok(SocketAddr::new(ip, 53))
+}
+
+fn format_rdata(data: &RData) -> String {
+    let value = match data {
+        RData::MX(mx) => format!("{} {}", mx.preference(), mx.exchange()),
+        RData::TXT(txt) => txt
+            .iter()
+            .map(|part| String::from_utf8_lossy(part).to_string())
+            .collect(),
+        data => data.to_string(),
+    };
+
+    normalize(&value)
+}
+
+/// Lowercases names and drops the trailing dot so answers compare equal to
+/// the values written in the URL
+fn normalize(value: &str) -> String {
+    value.trim().trim_end_matches('.').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_resolver::proto::{
+        op::{Message, MessageType},
+        rr::{rdata::A, Record},
+    };
+    use tokio::net::UdpSocket;
+
+    /// Answers every A query with 10.0.0.1 and 10.0.0.2
+    async fn spawn_stub_server() -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut buf = [0; 512];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                let request = Message::from_vec(&buf[..len]).unwrap();
+
+                let mut response = Message::new();
+                response
+                    .set_id(request.id())
+                    .set_message_type(MessageType::Response)
+                    .set_recursion_available(true)
+                    .add_queries(request.queries().to_vec());
+
+                let query = &request.queries()[0];
+                if query.query_type() == RecordType::A {
+                    for ip in [[10, 0, 0, 2], [10, 0, 0, 1]] {
+                        let record = Record::from_rdata(
+                            query.name().clone(),
+                            60,
+                            RData::A(A::new(ip[0], ip[1], ip[2], ip[3])),
+                        );
+                        response.add_answer(record);
+                    }
+                }
+
+                let bytes = response.to_vec().unwrap();
+                socket.send_to(&bytes, peer).await.unwrap();
+            }
+        });
+
+        addr
+    }
+
+    #[test]
+    fn test_parse() {
+        let url = Url::from(
+            "dns://Example.com?type=mx&expect=10%20mail.example.com.&resolver=127.0.0.1:5353"
+                .to_string(),
+        );
+
+        assert_eq!(
+            DnsCheck::parse(&url).unwrap(),
+            DnsCheck {
+                name: "example.com".to_string(),
+                record_type: RecordType::MX,
+                expect: vec!["10 mail.example.com".to_string()],
+                resolver: Some("127.0.0.1:5353".parse().unwrap()),
+            }
+        );
+
+        let url = Url::from("dns://example.com?type=SOA".to_string());
+        assert!(DnsCheck::parse(&url).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let addr = spawn_stub_server().await;
+        let url = Url::from(format!(
+            "dns://example.com?expect=10.0.0.1&resolver={}",
+            addr
+        ));
+        let check = DnsCheck::parse(&url).unwrap();
+
+        let answers = resolve(&check, Duration::from_secs(2)).await.unwrap();
+        assert_eq!(answers, vec!["10.0.0.1", "10.0.0.2"]);
+        assert!(check.matches(&answers));
+
+        let url = Url::from(format!("dns://example.com?type=TXT&resolver={}", addr));
+        let check = DnsCheck::parse(&url).unwrap();
+
+        let answers = resolve(&check, Duration::from_secs(2)).await.unwrap();
+        assert!(answers.is_empty());
+        assert!(!check.matches(&answers));
+    }
+}
@@ -0,0 +1,79 @@
+use chrono::{Local, NaiveDateTime};
+use serde::Serialize;
+use std::sync::LazyLock;
+use tokio::sync::broadcast;
+
+use crate::db::{endpoint::Status, url::Url};
+
+/// Events that aren't read by a slow subscriber in time are dropped for it
+const EVENTS_CAPACITY: usize = 256;
+
+pub static EVENTS: LazyLock<broadcast::Sender<Event>> =
+    LazyLock::new(|| broadcast::channel(EVENTS_CAPACITY).0);
+
+/// Live updates for the `/api/events` stream
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A check completed, `status` is the status after it
+    Check {
+        name: String,
+        url: String,
+        success: bool,
+        latency: Option<i64>,
+        status: Status,
+        checked_at: NaiveDateTime,
+    },
+    /// The status of an endpoint changed
+    Status {
+        name: String,
+        url: String,
+        status: Status,
+        previous: Status,
+        changed_at: NaiveDateTime,
+    },
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Check { .. } => "check",
+            Self::Status { .. } => "status",
+        }
+    }
+}
+
+/// Publishes the events of a completed check
+pub fn publish_check(
+    url: &Url,
+    success: bool,
+    latency: Option<i64>,
+    previous: Status,
+    status: Status,
+) {
+    let now = Local::now().naive_local();
+
+    if status != previous {
+        publish(Event::Status {
+            name: url.name().to_string(),
+            url: url.as_str().to_string(),
+            status,
+            previous,
+            changed_at: now,
+        });
+    }
+
+    publish(Event::Check {
+        name: url.name().to_string(),
+        url: url.as_str().to_string(),
+        success,
+        latency,
+        status,
+        checked_at: now,
+    });
+}
+
+fn publish(event: Event) {
+    // Sending only fails when nobody is subscribed
+    let _ = EVENTS.send(event);
+}
@@ -0,0 +1,107 @@
+use chrono::{Local, NaiveDateTime};
+use serde::Serialize;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock, Mutex,
+    },
+    time::Duration,
+};
+use tracing::{error, warn};
+
+use crate::{
+    constants::{get_monitor_heartbeat_interval, get_monitor_heartbeat_url},
+    leader::LEADER,
+    HEALTH_MAX_SCHEDULER_LAG,
+};
+
+pub static HEALTH: LazyLock<Health> = LazyLock::new(Health::default);
+
+/// The monitor's own health, reported on `/healthz` and used to decide
+/// whether the external heartbeat is pinged
+#[derive(Debug, Default)]
+pub struct Health {
+    /// How late the last check started compared to its schedule
+    scheduler_lag: AtomicU64,
+    /// The longest check interval, the db is written at least that often
+    max_interval: AtomicU64,
+    last_check_at: Mutex<Option<NaiveDateTime>>,
+    last_db_write_at: Mutex<Option<NaiveDateTime>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    /// Whether this instance holds the leader lease
+    pub leader: bool,
+    pub scheduler_lag_ms: u64,
+    pub last_check_at: Option<NaiveDateTime>,
+    pub last_db_write_at: Option<NaiveDateTime>,
+}
+
+impl Health {
+    pub fn record_check_started(&self, lag: Duration) {
+        self.scheduler_lag
+            .store(lag.as_millis() as u64, Ordering::Relaxed);
+        *self.last_check_at.lock().unwrap() = Some(Local::now().naive_local());
+    }
+
+    pub fn record_db_write(&self) {
+        *self.last_db_write_at.lock().unwrap() = Some(Local::now().naive_local());
+    }
+
+    pub fn set_max_interval(&self, interval: Duration) {
+        self.max_interval
+            .store(interval.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Healthy while checks start on time and their results are written. A
+    /// check or a write is overdue after twice the longest interval
+    pub fn report(&self) -> HealthReport {
+        let now = Local::now().naive_local();
+        let scheduler_lag_ms = self.scheduler_lag.load(Ordering::Relaxed);
+        let max_interval = self.max_interval.load(Ordering::Relaxed) as i64;
+        let max_age = chrono::Duration::milliseconds(max_interval * 2)
+            + chrono::Duration::milliseconds(HEALTH_MAX_SCHEDULER_LAG as i64);
+
+        let last_check_at = *self.last_check_at.lock().unwrap();
+        let last_db_write_at = *self.last_db_write_at.lock().unwrap();
+        let is_recent =
+            |time: Option<NaiveDateTime>| time.is_some_and(|time| now - time <= max_age);
+
+        HealthReport {
+            healthy: scheduler_lag_ms <= HEALTH_MAX_SCHEDULER_LAG
+                && is_recent(last_check_at)
+                && is_recent(last_db_write_at),
+            leader: LEADER.is_leader(),
+            scheduler_lag_ms,
+            last_check_at,
+            last_db_write_at,
+        }
+    }
+}
+
+/// Pings `MONITOR_HEARTBEAT_URL` while the monitor is healthy so an external
+/// service can alert when the pings stop
+pub fn create_monitor_heartbeat_cron() {
+    let Some(url) = get_monitor_heartbeat_url() else {
+        return;
+    };
+    let interval = Duration::from_millis(get_monitor_heartbeat_interval());
+    let client = reqwest::Client::new();
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+
+            if !HEALTH.report().healthy {
+                warn!("Monitor heartbeat skipped, the monitor is unhealthy");
+                continue;
+            }
+
+            if let Err(e) = client.get(&url).timeout(interval).send().await {
+                error!(error = %e, "Monitor heartbeat failed");
+            }
+        }
+    });
+}
@@ -0,0 +1,74 @@
+use anyhow::anyhow;
+use chrono::{Duration, NaiveDateTime};
+
+use crate::db::url::Url;
+
+const DEFAULT_GRACE: i64 = 60;
+
+/// A passive monitor parsed from a `heartbeat://` URL, e.g.
+/// `heartbeat://backups?period=86400&grace=3600`
+///
+/// The job pings `/ping/<name>` after every successful run and the monitor
+/// goes down when no ping arrives within `period` + `grace` seconds.
+#[derive(Debug, PartialEq)]
+pub struct HeartbeatCheck {
+    pub name: String,
+    pub period: Duration,
+    pub grace: Duration,
+}
+
+impl HeartbeatCheck {
+    pub fn parse(url: &Url) -> anyhow::Result<Self> {
+        let parsed = reqwest::Url::parse(url.as_str())?;
+        let name = parsed
+            .host_str()
+            .ok_or_else(|| anyhow!("{} is missing the heartbeat name", url))?
+            .to_string();
+
+        let mut period = None;
+        let mut grace = DEFAULT_GRACE;
+
+        for (key, value) in parsed.query_pairs() {
+            match key.as_ref() {
+                "period" => period = Some(value.parse()?),
+                "grace" => grace = value.parse()?,
+                _ => return Err(anyhow!("Unknown heartbeat option `{}` in {}", key, url)),
+            }
+        }
+
+        let period = period.ok_or_else(|| anyhow!("{} is missing the `period` option", url))?;
+
+        Ok(Self {
+            name,
+            period: Duration::seconds(period),
+            grace: Duration::seconds(grace),
+        })
+    }
+
+    /// Returns `true` if the next ping is overdue, `last` is the time of the
+    /// last ping or when the monitor was created
+    pub fn is_late(&self, last: NaiveDateTime, now: NaiveDateTime) -> bool {
+        now.signed_duration_since(last) > self.period + self.grace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat() {
+        let url = Url::from("heartbeat://backups?period=3600&grace=300".to_string());
+        let check = HeartbeatCheck::parse(&url).unwrap();
+
+        assert_eq!(check.name, "backups");
+
+        let last =
+            NaiveDateTime::parse_from_str("2024-06-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert!(!check.is_late(last, last + Duration::seconds(3900)));
+        assert!(check.is_late(last, last + Duration::seconds(3901)));
+
+        let url = Url::from("heartbeat://backups".to_string());
+        assert!(HeartbeatCheck::parse(&url).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, LazyLock,
+    },
+    time::Duration,
+};
+use teloxide::Bot;
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+use crate::{
+    bot::{notify, NotifyOpts},
+    constants::{get_instance_id, get_leader_lease},
+    db::{url::Url, Db},
+    metrics::METRICS,
+    tasks::track,
+};
+
+pub static LEADER: LazyLock<Leader> = LazyLock::new(Leader::new);
+
+/// Instances sharing a database elect a leader through a lease on the
+/// `metadata` row. Only the leader updates the statuses and sends the
+/// notifications, the standby keeps checking and takes over once the lease
+/// expires
+#[derive(Debug)]
+pub struct Leader {
+    instance: String,
+    lease: Duration,
+    is_leader: AtomicBool,
+}
+
+impl Leader {
+    fn new() -> Self {
+        Self {
+            instance: get_instance_id(),
+            lease: Duration::from_millis(get_leader_lease()),
+            is_leader: AtomicBool::new(false),
+        }
+    }
+
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.is_leader.load(Ordering::Relaxed)
+    }
+
+    /// Takes or renews the lease and returns whether this instance is the
+    /// leader. The leadership is dropped when the db can't be reached since
+    /// the lease can't be renewed either
+    pub async fn elect(&self, db: &Db) -> anyhow::Result<bool> {
+        let result = db.metadata.acquire_lease(&self.instance, self.lease).await;
+        let is_leader = *result.as_ref().unwrap_or(&false);
+        self.is_leader.store(is_leader, Ordering::Relaxed);
+
+        result
+    }
+
+    /// Lets the standby take over right away on a clean shutdown
+    pub async fn resign(&self, db: &Db) -> anyhow::Result<()> {
+        if self.is_leader.swap(false, Ordering::Relaxed) {
+            db.metadata.release_lease(&self.instance).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Renews the lease three times per lease period so it doesn't expire
+/// between two renewals, and announces when this instance takes over. The
+/// endpoints are reloaded on every renewal to pick up the ones changed
+/// through the API of the other instance
+pub fn create_leader_election_cron(
+    config: Vec<Url>,
+    urls: watch::Sender<Arc<Vec<Url>>>,
+    db: Arc<Db>,
+    bot: Arc<Bot>,
+) {
+    let interval = LEADER.lease / 3;
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+
+            // The lease is released on shutdown
+            let was_leader = LEADER.is_leader();
+            let Some(result) = track(LEADER.elect(&db)).await else {
+                break;
+            };
+            let is_leader = match result {
+                Ok(is_leader) => is_leader,
+                Err(e) => {
+                    METRICS.record_error(&e);
+                    error!(error = %e, "Couldn't renew the leader lease");
+                    false
+                }
+            };
+
+            if let Err(e) = reload_endpoints(&config, &urls, &db).await {
+                METRICS.record_error(&e);
+                error!(error = %e, "Couldn't reload the endpoints");
+            }
+
+            match (was_leader, is_leader) {
+                (false, true) => {
+                    info!(
+                        instance = LEADER.instance(),
+                        "This instance is now the leader"
+                    );
+
+                    let message = format!("🔄 {} took over as the leader", LEADER.instance());
+                    if let Err(e) = notify(&NotifyOpts { message, bot: &bot }).await {
+                        error!(error = %e, "Couldn't send the leader change notice");
+                    }
+                }
+                (true, false) => warn!(
+                    instance = LEADER.instance(),
+                    "This instance lost the leader lease and is now on standby"
+                ),
+                _ => {}
+            }
+        }
+    });
+}
+
+/// Replaces the endpoints when the ones created through the API changed in
+/// the db, the checks restart then. The order isn't compared since another
+/// instance can list them differently
+async fn reload_endpoints(
+    config: &[Url],
+    urls: &watch::Sender<Arc<Vec<Url>>>,
+    db: &Db,
+) -> anyhow::Result<()> {
+    let mut new_urls = config.to_vec();
+    new_urls.extend(db.endpoint.get_api_urls().await?);
+
+    let configured = |urls: &[Url]| {
+        let mut configured = urls
+            .iter()
+            .map(|url| (url.to_string(), url.options_string()))
+            .collect::<Vec<_>>();
+        configured.sort();
+        configured
+    };
+
+    urls.send_if_modified(|urls| {
+        let changed = configured(urls) != configured(&new_urls);
+        if changed {
+            info!("The endpoints changed in the database, reloading them");
+            *urls = Arc::new(new_urls);
+        }
+        changed
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reload_endpoints() {
+        let config = vec![Url::from("https://example.com".to_string())];
+        let db = Db::in_memory(&config).await.unwrap();
+        let (urls, mut urls_rx) = watch::channel(Arc::new(config.clone()));
+
+        reload_endpoints(&config, &urls, &db).await.unwrap();
+        assert!(!urls_rx.has_changed().unwrap());
+
+        // Created through the API of another instance
+        let api = Url::from("https://api.example.com#name=api".to_string());
+        db.endpoint.create(&api).await.unwrap();
+
+        reload_endpoints(&config, &urls, &db).await.unwrap();
+        assert!(urls_rx.has_changed().unwrap());
+        let names = urls_rx
+            .borrow_and_update()
+            .iter()
+            .map(|url| url.name().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["example.com", "api"]);
+    }
+}
@@ -0,0 +1,37 @@
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use crate::{telemetry::Telemetry, DEFAULT_LOG_FILTER};
+
+/// Sets up the logs, `RUST_LOG` filters them per module (e.g.
+/// `info,server_monitor::status=debug`) and `LOG_FORMAT=json` prints them
+/// as JSON lines. The spans are also exported over OTLP when it's enabled,
+/// the returned handle flushes them on shutdown
+pub fn init() -> anyhow::Result<Option<Telemetry>> {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let json = std::env::var("LOG_FORMAT").is_ok_and(|format| format == "json");
+
+    let (plain, json) = if json {
+        let layer = fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(true);
+        (None, Some(layer))
+    } else {
+        (Some(fmt::layer()), None)
+    };
+
+    let telemetry = Telemetry::init()?;
+    let otel = telemetry
+        .as_ref()
+        .map(|telemetry| tracing_opentelemetry::layer().with_tracer(telemetry.tracer()));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(plain)
+        .with(json)
+        .with(otel)
+        .init();
+
+    Ok(telemetry)
+}
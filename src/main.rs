@@ -1,21 +1,99 @@
+mod agent;
 mod bot;
+mod check;
 mod constants;
 mod db;
+mod dependency;
+mod dns;
+mod events;
+mod health;
+mod heartbeat;
+mod leader;
+mod logging;
+mod maintenance;
+mod metrics;
+mod policy;
+mod probe;
+mod quorum;
+mod scheduler;
+mod server;
 mod status;
+mod tasks;
+mod telemetry;
+mod tls;
 
-use bot::create_bot;
-use constants::get_interval;
+use bot::{create_bot, notify, NotifyOpts};
+use chrono::Local;
+use constants::{
+    get_instance_name, get_interval, get_max_body_size, get_max_concurrent_checks,
+    get_shutdown_timeout, get_snapshot_body_size, is_agent,
+};
 use db::{url::Url, Db};
-use status::{check_url_status, create_server_update_cron};
-use std::{sync::Arc, time::Duration};
+use health::{create_monitor_heartbeat_cron, HEALTH};
+use leader::{create_leader_election_cron, LEADER};
+use maintenance::get_maintenance_windows;
+use metrics::METRICS;
+use policy::CheckPolicy;
+use scheduler::Scheduler;
+use server::create_server;
+use status::{
+    check_url_status, create_cert_check_cron, create_maintenance_cron, create_server_update_cron,
+};
+use std::{process::ExitCode, sync::Arc, time::Duration};
+use tasks::TASKS;
 use teloxide::Bot;
+use tokio::sync::watch;
+use tracing::{error, info, info_span, warn, Instrument};
 
 const DEFAULT_INTERVAL: u64 = 1000 * 60; // 1 minute
+const DEFAULT_TIMEOUT: u64 = 10; // 10 seconds
+const DEFAULT_TRIES: u8 = 2;
 const UPDATE_INTERVAL: u64 = 1000 * 60 * 60 * 24; // 24 hours
+const DEFAULT_CERT_CHECK_INTERVAL: u64 = 1000 * 60 * 60 * 6; // 6 hours
+const DEFAULT_CERT_WARN_DAYS: &str = "30,14,7,1";
+const DEFAULT_DOWN_THRESHOLD: i64 = 1;
+const DEFAULT_UP_THRESHOLD: i64 = 1;
+const DEFAULT_FLAP_THRESHOLD: i64 = 5;
+const DEFAULT_FLAP_WINDOW: u64 = 1000 * 60 * 30; // 30 minutes
+const MAINTENANCE_CHECK_INTERVAL: u64 = 1000 * 60; // 1 minute
+const DEFAULT_MAX_CONCURRENT_CHECKS: usize = 10;
+const CHECK_RETENTION_DAYS: i64 = 90;
+const INCIDENT_RETENTION_DAYS: i64 = 365;
+const DEFAULT_STATUS_PAGE_TITLE: &str = "Status";
+const DEFAULT_STATUS_PAGE_COLOR: &str = "#2563eb";
+const STATUS_PAGE_REFRESH_INTERVAL: u64 = 1000 * 60; // 1 minute
+const STATUS_PAGE_DAYS: i64 = 90;
+const DEFAULT_MONITOR_HEARTBEAT_INTERVAL: u64 = 1000 * 60; // 1 minute
+const HEALTH_MAX_SCHEDULER_LAG: u64 = 1000 * 60; // 1 minute
+const DEFAULT_LOG_FILTER: &str = "info";
+const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 1000 * 8; // 8 seconds, Docker kills after 10
+const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024 * 10; // 10 MiB
+const DEFAULT_SNAPSHOT_BODY_SIZE: usize = 1024 * 4; // 4 KiB
+const DEFAULT_OTEL_SERVICE_NAME: &str = "server-monitor";
+const DEFAULT_LOCATION: &str = "local";
+const AGENT_REFRESH_INTERVAL: u64 = 1000 * 60; // 1 minute
+const AGENT_CONNECT_TIMEOUT: u64 = 1000 * 5; // 5 seconds
+const AGENT_REQUEST_TIMEOUT: u64 = 1000 * 30; // 30 seconds
+const DEFAULT_LEADER_LEASE: u64 = 1000 * 30; // 30 seconds
+const DEFAULT_DATABASE_URL: &str = "sqlite:db/db.sqlite";
+const SQLITE_BUSY_TIMEOUT: u64 = 1000 * 5; // 5 seconds
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() -> anyhow::Result<ExitCode> {
     dotenvy::dotenv().ok();
+    let telemetry = logging::init()?;
+
+    // Invalid limits fail here instead of on the first check
+    get_max_body_size();
+    get_snapshot_body_size();
+
+    if is_agent() {
+        let exit_code = agent::run().await;
+        if let Some(telemetry) = telemetry {
+            telemetry.shutdown();
+        }
+        return exit_code;
+    }
 
     let urls: Vec<Url> = std::env::var("URLS")
         .expect("URLS must be set")
@@ -27,43 +105,207 @@ async fn main() -> anyhow::Result<()> {
     let bot = Arc::new(create_bot());
     let db = Arc::new(Db::new(&urls).await?);
 
-    println!("\nServer monitor is running with the following settings:");
-    println!("\n- Interval: {}ms", interval);
-    println!("- URLs:");
-    urls.iter().for_each(|url| {
-        println!("  - {}", url);
-    });
+    let is_leader = LEADER.elect(&db).await?;
+    info!(
+        instance = LEADER.instance(),
+        leader = is_leader,
+        "Leader election completed"
+    );
 
-    create_server_update_cron(Arc::clone(&db), Arc::clone(&bot)).await?;
+    let instance = get_instance_name();
+    if db.metadata.start(&instance).await? && is_leader {
+        let message = "⚠️ The monitor restarted after an unclean shutdown".to_string();
+        warn!("The previous run didn't shut down cleanly");
+        if let Err(e) = notify(&NotifyOpts { message, bot: &bot }).await {
+            error!(error = %e, "Couldn't send the unclean shutdown notice");
+        }
+    }
 
-    let mut handles = Vec::new();
-    urls.iter().for_each(|url| {
-        let bot = Arc::clone(&bot);
-        let db = Arc::clone(&db);
-        let url = url.clone();
+    // The endpoints created through the API are monitored along with `URLS`
+    let config = urls.clone();
+    let mut urls = urls;
+    urls.extend(db.endpoint.get_api_urls().await?);
 
-        let handle = tokio::spawn(async move {
-            create_url_check_cron(&url, bot, db).await.unwrap();
-        });
+    dependency::validate(&urls)?;
 
-        handles.push(handle);
-    });
+    info!(
+        interval,
+        maintenance_windows = get_maintenance_windows().len(),
+        "Server monitor is running"
+    );
+    for url in urls.iter() {
+        let policy = CheckPolicy::from_url(url)?;
+        info!(
+            endpoint = url.name(),
+            url = url.as_str(),
+            interval = policy.interval.as_millis() as u64,
+            "Monitoring"
+        );
+    }
 
-    futures::future::join_all(handles).await;
+    let (urls_tx, mut urls_rx) = watch::channel(Arc::new(urls));
 
-    Ok(())
-}
+    create_server_update_cron(Arc::clone(&db), Arc::clone(&bot)).await?;
+    create_cert_check_cron(urls_tx.subscribe(), Arc::clone(&db), Arc::clone(&bot));
+    create_maintenance_cron(urls_tx.subscribe(), Arc::clone(&db), Arc::clone(&bot));
+    create_server(urls_tx.clone(), Arc::clone(&db), Arc::clone(&bot)).await?;
+    create_monitor_heartbeat_cron();
+    create_leader_election_cron(config, urls_tx.clone(), Arc::clone(&db), Arc::clone(&bot));
 
-async fn create_url_check_cron(url: &Url, bot: Arc<Bot>, db: Arc<Db>) -> anyhow::Result<()> {
-    let interval = get_interval();
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut exit_code = ExitCode::SUCCESS;
 
     loop {
-        let result = check_url_status(&url, &bot, &db).await;
+        let urls = urls_rx.borrow_and_update().clone();
+        let policies = urls
+            .iter()
+            .map(CheckPolicy::from_url)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let max_interval = policies
+            .iter()
+            .map(|policy| policy.longest_interval())
+            .max();
+        HEALTH.set_max_interval(max_interval.unwrap_or_default());
+
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let checks = create_url_check_cron(
+            urls,
+            Arc::new(policies),
+            Arc::clone(&bot),
+            Arc::clone(&db),
+            stop_rx,
+        );
+        tokio::pin!(checks);
+
+        let result = tokio::select! {
+            _ = &mut checks => break,
+            _ = urls_rx.changed() => {
+                info!("The endpoints changed, restarting the checks once the running ones are done");
+                stop_tx.send_replace(true);
+
+                // The next scheduler starts once this one is done, so the
+                // concurrency cap holds and checks of an endpoint don't overlap
+                tokio::select! {
+                    _ = &mut checks => continue,
+                    result = &mut shutdown => result,
+                }
+            }
+            result = &mut shutdown => result,
+        };
 
-        if let Err(e) = result {
-            eprintln!("Error: {}", e);
+        result?;
+        info!("Shutting down, waiting for the running checks");
+        stop_tx.send_replace(true);
+        TASKS.close();
+
+        // The pings and the crons are in `TASKS` too
+        let drain = async {
+            checks.await;
+            TASKS.wait().await;
+        };
+        let timeout = Duration::from_millis(get_shutdown_timeout());
+        if tokio::time::timeout(timeout, drain).await.is_err() {
+            error!("The running checks didn't finish in time");
+            exit_code = ExitCode::FAILURE;
         }
 
-        tokio::time::sleep(Duration::from_millis(interval)).await;
+        break;
+    }
+
+    if let Err(e) = LEADER.resign(&db).await {
+        METRICS.record_error(&e);
+        error!(error = %e, "Couldn't release the leader lease");
+    }
+
+    // Without the marker the next start reports an unclean shutdown
+    if exit_code == ExitCode::SUCCESS {
+        db.metadata.stop(&instance).await?;
     }
+    db.pool.close().await;
+
+    info!("Server monitor stopped");
+
+    if let Some(telemetry) = telemetry {
+        telemetry.shutdown();
+    }
+
+    Ok(exit_code)
+}
+
+/// Resolves on Ctrl-C or SIGTERM
+async fn shutdown_signal() -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate())?;
+
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+
+    Ok(())
+}
+
+/// Checks every URL from a single scheduler, the interval until the next
+/// check depends on the URL's policy and its current status
+async fn create_url_check_cron(
+    urls: Arc<Vec<Url>>,
+    policies: Arc<Vec<CheckPolicy>>,
+    bot: Arc<Bot>,
+    db: Arc<Db>,
+    shutdown: watch::Receiver<bool>,
+) {
+    let intervals = policies.iter().map(|policy| policy.interval).collect();
+
+    let scheduler = Scheduler::new(intervals, get_max_concurrent_checks());
+    scheduler
+        .run(
+            |i| {
+                let urls = Arc::clone(&urls);
+                let policies = Arc::clone(&policies);
+                let bot = Arc::clone(&bot);
+                let db = Arc::clone(&db);
+
+                let span = info_span!(
+                    "check",
+                    endpoint = urls[i].name(),
+                    url = urls[i].as_str(),
+                    latency = tracing::field::Empty,
+                    outcome = tracing::field::Empty,
+                    status = tracing::field::Empty,
+                );
+
+                async move {
+                    let url = &urls[i];
+                    let result = check_url_status(url, &urls, &bot, &db).await;
+
+                    if let Err(e) = result {
+                        METRICS.record_error(&e);
+                        error!(error = %e, "Check failed");
+                    }
+
+                    match db.endpoint.get(url).await {
+                        Ok(endpoint) => {
+                            policies[i].next_interval(&endpoint, Local::now().naive_local())
+                        }
+                        Err(e) => {
+                            METRICS.record_error(&e);
+                            error!(error = %e, "Couldn't get the endpoint status");
+                            policies[i].interval
+                        }
+                    }
+                }
+                .instrument(span)
+            },
+            shutdown,
+        )
+        .await;
 }
@@ -0,0 +1,216 @@
+use anyhow::anyhow;
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Weekday};
+
+use crate::db::url::Url;
+
+/// Which endpoints a maintenance window applies to
+#[derive(Debug, PartialEq)]
+pub enum Target {
+    All,
+    Tag(String),
+    /// An endpoint by its name or URL
+    Endpoint(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Schedule {
+    Once(NaiveDateTime),
+    Daily(NaiveTime),
+    Weekly(Weekday, NaiveTime),
+}
+
+/// A scheduled maintenance during which checks still run but alerts are
+/// suppressed, e.g. `tag:prod sun@03:00 30m` or `api 2024-06-01T02:00 2h`
+#[derive(Debug, PartialEq)]
+pub struct MaintenanceWindow {
+    pub target: Target,
+    pub schedule: Schedule,
+    pub duration: Duration,
+}
+
+impl MaintenanceWindow {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        let parts = value.split_whitespace().collect::<Vec<_>>();
+        let [target, schedule, duration] = parts[..] else {
+            return Err(anyhow!(
+                "Invalid maintenance window `{}`, expected `<target> <schedule> <duration>`",
+                value
+            ));
+        };
+
+        let target = match target {
+            "*" => Target::All,
+            target => match target.strip_prefix("tag:") {
+                Some(tag) => Target::Tag(tag.to_string()),
+                None => Target::Endpoint(target.to_string()),
+            },
+        };
+
+        let schedule = match schedule.split_once('@') {
+            Some(("daily", time)) => Schedule::Daily(parse_time(time)?),
+            Some((day, time)) => Schedule::Weekly(
+                day.parse()
+                    .map_err(|_| anyhow!("Invalid maintenance day `{}`", day))?,
+                parse_time(time)?,
+            ),
+            None => Schedule::Once(NaiveDateTime::parse_from_str(schedule, "%Y-%m-%dT%H:%M")?),
+        };
+
+        Ok(Self {
+            target,
+            schedule,
+            duration: parse_duration(duration)?,
+        })
+    }
+
+    pub fn applies_to(&self, url: &Url) -> bool {
+        match &self.target {
+            Target::All => true,
+            Target::Tag(tag) => url.tags().contains(&tag.as_str()),
+            Target::Endpoint(name) => {
+                url.name() == name || url.as_str() == name || url.strip_prefix() == name
+            }
+        }
+    }
+
+    /// Returns when the window that covers `now` started, `None` if the
+    /// window isn't active
+    pub fn active_since(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let is_active = |start: NaiveDateTime| start <= now && now < start + self.duration;
+
+        match self.schedule {
+            Schedule::Once(start) => Some(start).filter(|&start| is_active(start)),
+            Schedule::Daily(time) | Schedule::Weekly(_, time) => {
+                // A recurring window can start on an earlier day and still
+                // be running
+                (0..=self.duration.num_days() + 7)
+                    .map(|days| (now.date() - Duration::days(days)).and_time(time))
+                    .filter(|start| match self.schedule {
+                        Schedule::Weekly(day, _) => start.weekday() == day,
+                        _ => true,
+                    })
+                    .find(|&start| is_active(start))
+            }
+        }
+    }
+
+    pub fn is_active(&self, now: NaiveDateTime) -> bool {
+        self.active_since(now).is_some()
+    }
+
+    /// When the window that started at `start` ends
+    pub fn end(&self, start: NaiveDateTime) -> NaiveDateTime {
+        start + self.duration
+    }
+}
+
+/// The maintenance windows from the comma separated `MAINTENANCE` env variable
+pub fn get_maintenance_windows() -> Vec<MaintenanceWindow> {
+    std::env::var("MAINTENANCE")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|window| !window.trim().is_empty())
+        .map(|window| MaintenanceWindow::parse(window).expect("MAINTENANCE is invalid"))
+        .collect()
+}
+
+/// Returns `true` if the URL is in an active maintenance window
+pub fn is_in_maintenance(url: &Url, now: NaiveDateTime) -> bool {
+    get_maintenance_windows()
+        .iter()
+        .any(|window| window.applies_to(url) && window.is_active(now))
+}
+
+fn parse_time(value: &str) -> anyhow::Result<NaiveTime> {
+    Ok(NaiveTime::parse_from_str(value, "%H:%M")?)
+}
+
+/// Parses durations like `30m`, `2h` or `1d`
+fn parse_duration(value: &str) -> anyhow::Result<Duration> {
+    let invalid = || anyhow!("Invalid maintenance duration `{}`", value);
+
+    let (index, unit) = value.char_indices().last().ok_or_else(invalid)?;
+    let amount = value[..index].parse::<i64>().map_err(|_| invalid())?;
+
+    if amount <= 0 {
+        return Err(invalid());
+    }
+
+    match unit {
+        'm' => Duration::try_minutes(amount),
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        _ => None,
+    }
+    .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            MaintenanceWindow::parse("api 2024-06-01T02:00 2h").unwrap(),
+            MaintenanceWindow {
+                target: Target::Endpoint("api".to_string()),
+                schedule: Schedule::Once(datetime("2024-06-01 02:00")),
+                duration: Duration::hours(2),
+            }
+        );
+
+        assert_eq!(
+            MaintenanceWindow::parse("tag:prod sun@03:00 30m").unwrap(),
+            MaintenanceWindow {
+                target: Target::Tag("prod".to_string()),
+                schedule: Schedule::Weekly(Weekday::Sun, NaiveTime::from_hms_opt(3, 0, 0).unwrap()),
+                duration: Duration::minutes(30),
+            }
+        );
+
+        assert!(MaintenanceWindow::parse("* daily@25:00 1h").is_err());
+        assert!(MaintenanceWindow::parse("* daily@03:00").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::days(1));
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("1µ").is_err());
+        assert!(parse_duration("µ").is_err());
+        assert!(parse_duration("-5m").is_err());
+        assert!(parse_duration("0h").is_err());
+        assert!(parse_duration("9999999999999999d").is_err());
+    }
+
+    #[test]
+    fn test_is_active() {
+        let window = MaintenanceWindow::parse("* daily@23:30 1h").unwrap();
+
+        assert!(window.is_active(datetime("2024-06-01 23:45")));
+        assert!(window.is_active(datetime("2024-06-02 00:15")));
+        assert!(!window.is_active(datetime("2024-06-02 00:30")));
+        assert_eq!(
+            window.active_since(datetime("2024-06-02 00:15")),
+            Some(datetime("2024-06-01 23:30"))
+        );
+
+        // 2024-06-02 is a Sunday
+        let window = MaintenanceWindow::parse("* sun@03:00 30m").unwrap();
+
+        assert!(window.is_active(datetime("2024-06-02 03:10")));
+        assert!(!window.is_active(datetime("2024-06-03 03:10")));
+
+        let window = MaintenanceWindow::parse("* 2024-06-01T02:00 2h").unwrap();
+
+        assert!(window.is_active(datetime("2024-06-01 03:59")));
+        assert!(!window.is_active(datetime("2024-06-01 04:00")));
+    }
+}
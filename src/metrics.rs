@@ -0,0 +1,180 @@
+use chrono::Local;
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+
+use crate::db::{endpoint::Status, url::Url, Db};
+
+const NAMESPACE: &str = "server_monitor";
+const ENDPOINT_LABELS: &[&str] = &["name", "tags"];
+const LATENCY_BUCKETS: &[f64] = &[0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// The metrics exposed on `/metrics`. Counters and histograms are updated as
+/// things happen, the gauges are read from the db on every scrape
+pub struct Metrics {
+    registry: Registry,
+    up: IntGaugeVec,
+    latency: GaugeVec,
+    latency_histogram: HistogramVec,
+    checks: IntCounterVec,
+    certificate_expiry: IntGaugeVec,
+    incidents: IntCounterVec,
+    notification_failures: IntCounter,
+    db_errors: IntCounter,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some(NAMESPACE.to_string()), None)
+            .expect("The metrics namespace is valid");
+
+        let up = IntGaugeVec::new(
+            Opts::new("endpoint_up", "1 if the endpoint is up, 0 if it's down"),
+            ENDPOINT_LABELS,
+        )
+        .unwrap();
+        let latency = GaugeVec::new(
+            Opts::new("endpoint_latency_seconds", "Latency of the last check"),
+            ENDPOINT_LABELS,
+        )
+        .unwrap();
+        let latency_histogram = HistogramVec::new(
+            HistogramOpts::new("check_latency_seconds", "Latency of the checks")
+                .buckets(LATENCY_BUCKETS.to_vec()),
+            ENDPOINT_LABELS,
+        )
+        .unwrap();
+        let checks = IntCounterVec::new(
+            Opts::new("checks_total", "Checks by outcome"),
+            &["name", "tags", "outcome"],
+        )
+        .unwrap();
+        let certificate_expiry = IntGaugeVec::new(
+            Opts::new(
+                "certificate_expiry_timestamp_seconds",
+                "Expiry of the TLS certificate as a Unix timestamp",
+            ),
+            ENDPOINT_LABELS,
+        )
+        .unwrap();
+        let incidents = IntCounterVec::new(
+            Opts::new("incidents_total", "Incidents since the monitor started"),
+            ENDPOINT_LABELS,
+        )
+        .unwrap();
+        let notification_failures = IntCounter::new(
+            "notification_failures_total",
+            "Telegram messages that couldn't be sent",
+        )
+        .unwrap();
+        let db_errors = IntCounter::new("db_errors_total", "Failed database queries").unwrap();
+
+        registry.register(Box::new(up.clone())).unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+        registry
+            .register(Box::new(latency_histogram.clone()))
+            .unwrap();
+        registry.register(Box::new(checks.clone())).unwrap();
+        registry
+            .register(Box::new(certificate_expiry.clone()))
+            .unwrap();
+        registry.register(Box::new(incidents.clone())).unwrap();
+        registry
+            .register(Box::new(notification_failures.clone()))
+            .unwrap();
+        registry.register(Box::new(db_errors.clone())).unwrap();
+
+        Self {
+            registry,
+            up,
+            latency,
+            latency_histogram,
+            checks,
+            certificate_expiry,
+            incidents,
+            notification_failures,
+            db_errors,
+        }
+    }
+
+    /// Records the outcome of a check, `latency` is in milliseconds
+    pub fn record_check(&self, url: &Url, success: bool, latency: Option<i64>) {
+        let tags = url.tags().join(",");
+        let outcome = if success { "success" } else { "failure" };
+
+        self.checks
+            .with_label_values(&[url.name(), &tags, outcome])
+            .inc();
+
+        if let Some(latency) = latency {
+            let seconds = latency as f64 / 1000.0;
+            let labels = [url.name(), &tags];
+
+            self.latency.with_label_values(&labels).set(seconds);
+            self.latency_histogram
+                .with_label_values(&labels)
+                .observe(seconds);
+        }
+    }
+
+    pub fn record_incident(&self, url: &Url) {
+        let tags = url.tags().join(",");
+        self.incidents.with_label_values(&[url.name(), &tags]).inc();
+    }
+
+    pub fn record_notification_failure(&self) {
+        self.notification_failures.inc();
+    }
+
+    /// Counts the error if it comes from the database
+    pub fn record_error(&self, error: &anyhow::Error) {
+        if error.downcast_ref::<sqlx::Error>().is_some() {
+            self.db_errors.inc();
+        }
+    }
+
+    /// Refreshes the gauges from the db and encodes all the metrics in the
+    /// Prometheus text format
+    pub async fn render(&self, urls: &[Url], db: &Db) -> anyhow::Result<String> {
+        let endpoints = db.endpoint.get_all().await?;
+        let certificates = db.certificate.get_all().await?;
+
+        // Removed endpoints shouldn't be reported anymore
+        self.up.reset();
+        self.certificate_expiry.reset();
+
+        for url in urls.iter() {
+            let tags = url.tags().join(",");
+            let labels = [url.name(), &tags];
+
+            if let Some(endpoint) = endpoints.iter().find(|e| e.url.as_str() == url.as_str()) {
+                match endpoint.status {
+                    Status::Up => self.up.with_label_values(&labels).set(1),
+                    Status::Down => self.up.with_label_values(&labels).set(0),
+                    Status::Pending => {}
+                }
+            }
+
+            // The expiry is stored in local time
+            let expires_at = certificates
+                .iter()
+                .find(|c| c.url == url.as_str())
+                .and_then(|c| c.expires_at.and_local_timezone(Local).earliest());
+
+            if let Some(expires_at) = expires_at {
+                self.certificate_expiry
+                    .with_label_values(&labels)
+                    .set(expires_at.timestamp());
+            }
+        }
+
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+
+        Ok(String::from_utf8(buffer)?)
+    }
+}
@@ -0,0 +1,228 @@
+use chrono::{Duration as ChronoDuration, NaiveDateTime};
+use std::time::Duration;
+
+use crate::{
+    constants::get_interval,
+    db::{
+        endpoint::{Endpoint, Status},
+        url::Url,
+    },
+};
+
+/// How often an endpoint is checked depending on its state, set with the
+/// URL options:
+///
+/// - `interval` - Interval in milliseconds, defaults to `INTERVAL`
+/// - `down_interval` - Interval in milliseconds while the endpoint is down
+/// - `backoff` - Multiplies the down interval after every failed check
+/// - `max_interval` - Limit of the backoff, defaults to the normal interval
+/// - `stable_interval` - Interval once the endpoint has been up for `stable_after` milliseconds
+#[derive(Debug, PartialEq)]
+pub struct CheckPolicy {
+    pub interval: Duration,
+    pub down_interval: Option<Duration>,
+    pub backoff: Option<f64>,
+    pub max_interval: Option<Duration>,
+    pub stable_interval: Option<(Duration, ChronoDuration)>,
+}
+
+impl CheckPolicy {
+    pub fn from_url(url: &Url) -> anyhow::Result<Self> {
+        let millis = |key| -> anyhow::Result<Option<u64>> {
+            url.option(key)
+                .map(|value| value.parse::<u64>())
+                .transpose()
+                .map_err(|_| anyhow::anyhow!("{} of {} must be a number", key, url.name()))
+        };
+
+        // A zero interval would check the endpoint in a loop
+        let interval = |key| -> anyhow::Result<Option<u64>> {
+            match millis(key)? {
+                Some(0) => Err(anyhow::anyhow!(
+                    "{} of {} must be greater than 0",
+                    key,
+                    url.name()
+                )),
+                value => Ok(value),
+            }
+        };
+
+        // Below 1 the backoff would shorten the down interval instead
+        let backoff = url
+            .option("backoff")
+            .map(|value| match value.parse::<f64>() {
+                Ok(backoff) if backoff.is_finite() && backoff >= 1.0 => Ok(backoff),
+                Ok(_) => Err(anyhow::anyhow!(
+                    "backoff of {} must be at least 1",
+                    url.name()
+                )),
+                Err(_) => Err(anyhow::anyhow!(
+                    "backoff of {} must be a number",
+                    url.name()
+                )),
+            })
+            .transpose()?;
+
+        let stable_interval = match (interval("stable_interval")?, millis("stable_after")?) {
+            (Some(interval), Some(after)) => Some((
+                Duration::from_millis(interval),
+                ChronoDuration::milliseconds(after as i64),
+            )),
+            (None, None) => None,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "stable_interval and stable_after of {} must be set together",
+                    url.name()
+                ))
+            }
+        };
+
+        Ok(Self {
+            interval: Duration::from_millis(interval("interval")?.unwrap_or_else(get_interval)),
+            down_interval: interval("down_interval")?.map(Duration::from_millis),
+            backoff,
+            max_interval: interval("max_interval")?.map(Duration::from_millis),
+            stable_interval,
+        })
+    }
+
+    /// The longest interval the endpoint can be checked at
+    pub fn longest_interval(&self) -> Duration {
+        [
+            Some(self.interval),
+            self.down_interval,
+            self.max_interval.filter(|_| self.backoff.is_some()),
+            self.stable_interval.map(|(interval, _)| interval),
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(self.interval)
+    }
+
+    /// The interval until the next check of the endpoint
+    pub fn next_interval(&self, endpoint: &Endpoint, now: NaiveDateTime) -> Duration {
+        match endpoint.status {
+            Status::Down => {
+                let interval = self.down_interval.unwrap_or(self.interval);
+
+                match self.backoff {
+                    Some(backoff) => {
+                        let exponent = (endpoint.failures - 1).clamp(0, 32) as i32;
+                        let max_interval = self.max_interval.unwrap_or(self.interval);
+
+                        interval.mul_f64(backoff.powi(exponent)).min(max_interval)
+                    }
+                    None => interval,
+                }
+            }
+            Status::Up => match (self.stable_interval, endpoint.uptime_at) {
+                (Some((interval, after)), Some(uptime_at))
+                    if now.signed_duration_since(uptime_at) >= after =>
+                {
+                    interval
+                }
+                _ => self.interval,
+            },
+            Status::Pending => self.interval,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::url::Url;
+
+    fn endpoint(status: Status, failures: i64, uptime_at: Option<NaiveDateTime>) -> Endpoint {
+        Endpoint {
+            id: String::new(),
+            url: Url::from("https://example.com".to_string()),
+            status,
+            uptime_at,
+            max_latency: None,
+            created_at: NaiveDateTime::default(),
+            failures,
+            successes: 0,
+            flapping_since: None,
+            unreachable_via: None,
+            options: String::new(),
+            source: "env".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_next_interval() {
+        let url = Url::from(
+            "https://example.com#interval=60000&down_interval=10000&backoff=2&max_interval=300000&stable_interval=300000&stable_after=86400000"
+                .to_string(),
+        );
+        let policy = CheckPolicy::from_url(&url).unwrap();
+        let now = NaiveDateTime::default() + ChronoDuration::days(30);
+        let seconds = |s| Duration::from_secs(s);
+
+        assert_eq!(
+            policy.next_interval(&endpoint(Status::Pending, 0, None), now),
+            seconds(60)
+        );
+        assert_eq!(
+            policy.next_interval(&endpoint(Status::Down, 1, None), now),
+            seconds(10)
+        );
+        assert_eq!(
+            policy.next_interval(&endpoint(Status::Down, 3, None), now),
+            seconds(40)
+        );
+        assert_eq!(
+            policy.next_interval(&endpoint(Status::Down, 10, None), now),
+            seconds(300)
+        );
+
+        let recently = Some(now - ChronoDuration::hours(1));
+        let long_ago = Some(now - ChronoDuration::days(2));
+        assert_eq!(
+            policy.next_interval(&endpoint(Status::Up, 0, recently), now),
+            seconds(60)
+        );
+        assert_eq!(
+            policy.next_interval(&endpoint(Status::Up, 0, long_ago), now),
+            seconds(300)
+        );
+
+        let url = Url::from("https://example.com#stable_interval=300000".to_string());
+        assert!(CheckPolicy::from_url(&url).is_err());
+
+        let url = Url::from("https://example.com#interval=0".to_string());
+        assert!(CheckPolicy::from_url(&url).is_err());
+    }
+
+    #[test]
+    fn test_from_url_rejects_invalid_options() {
+        let error = |options: &str| {
+            let url = Url::from(format!("https://example.com#name=example&{}", options));
+            CheckPolicy::from_url(&url).unwrap_err().to_string()
+        };
+
+        for backoff in ["-1", "0", "0.5", "NaN", "inf"] {
+            assert_eq!(
+                error(&format!("backoff={}", backoff)),
+                "backoff of example must be at least 1"
+            );
+        }
+        assert_eq!(error("backoff=fast"), "backoff of example must be a number");
+
+        for key in ["interval", "down_interval", "max_interval"] {
+            assert_eq!(
+                error(&format!("{}=0", key)),
+                format!("{} of example must be greater than 0", key)
+            );
+        }
+        assert_eq!(
+            error("stable_interval=0&stable_after=1000"),
+            "stable_interval of example must be greater than 0"
+        );
+
+        let url = Url::from("https://example.com#backoff=1.5".to_string());
+        assert_eq!(CheckPolicy::from_url(&url).unwrap().backoff, Some(1.5));
+    }
+}
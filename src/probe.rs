@@ -0,0 +1,830 @@
+use http_body_util::{BodyExt, Empty};
+use hyper::{
+    body::{Body, Bytes},
+    header::{
+        HeaderName, HeaderValue, ACCEPT, CONNECTION, HOST, LOCATION, PROXY_AUTHORIZATION,
+        USER_AGENT,
+    },
+    HeaderMap, Request, StatusCode, Uri, Version,
+};
+use hyper_util::{
+    client::proxy::matcher::{Intercept, Matcher},
+    rt::{TokioExecutor, TokioIo},
+};
+use opentelemetry::propagation::Injector;
+use reqwest::Url;
+use serde::Serialize;
+use std::{
+    fmt, io,
+    net::SocketAddr,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_native_tls::TlsConnector;
+use tracing::{info_span, Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::{
+    constants::{get_max_body_size, get_snapshot_body_size},
+    db::endpoint::Failure,
+};
+
+const MAX_REDIRECTS: usize = 10;
+const PROBE_USER_AGENT: &str = concat!("server-monitor/", env!("CARGO_PKG_VERSION"));
+/// Response headers that can carry credentials, they're left out of the
+/// snapshots
+const REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "x-auth-token",
+];
+
+/// Verifies the certificates against the system's roots, like the browsers
+/// and `curl` on the same host. HTTP/2 is used when the server offers it
+static TLS_CONNECTOR: LazyLock<TlsConnector> = LazyLock::new(|| {
+    native_tls::TlsConnector::builder()
+        .request_alpns(&["h2", "http/1.1"])
+        .build()
+        .expect("The system's TLS library can be loaded")
+        .into()
+});
+
+/// Connects to HTTPS proxies, they only speak HTTP/1.1
+static PROXY_TLS_CONNECTOR: LazyLock<TlsConnector> = LazyLock::new(|| {
+    native_tls::TlsConnector::new()
+        .expect("The system's TLS library can be loaded")
+        .into()
+});
+
+/// `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY`, read once
+static PROXIES: LazyLock<Matcher> = LazyLock::new(Matcher::from_env);
+
+/// A connection to the server, or to the proxy in front of it
+trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+/// Milliseconds spent in each phase of the last request, a phase is `None`
+/// when the request failed before it (or for TLS, over plain HTTP). Through
+/// a proxy, DNS and connect are the proxy's and connect includes the tunnel
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Timings {
+    pub dns: Option<i64>,
+    pub connect: Option<i64>,
+    pub tls: Option<i64>,
+    /// From sending the request to receiving the response head
+    pub ttfb: Option<i64>,
+}
+
+impl Timings {
+    /// The request failed before any phase completed
+    pub fn is_empty(&self) -> bool {
+        [self.dns, self.connect, self.tls, self.ttfb]
+            .iter()
+            .all(Option::is_none)
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phases = [
+            ("DNS", self.dns),
+            ("connect", self.connect),
+            ("TLS", self.tls),
+            ("TTFB", self.ttfb),
+        ]
+        .into_iter()
+        .filter_map(|(phase, latency)| Some(format!("{} {}ms", phase, latency?)))
+        .collect::<Vec<_>>();
+
+        write!(f, "{}", phases.join(", "))
+    }
+}
+
+fn elapsed(start: Instant) -> Option<i64> {
+    Some(start.elapsed().as_millis() as i64)
+}
+
+/// Why a probe failed, the detail is a short description of the error
+#[derive(Debug)]
+pub struct ProbeError {
+    pub failure: Failure,
+    pub detail: String,
+}
+
+impl ProbeError {
+    fn new(failure: Failure, detail: impl fmt::Display) -> Self {
+        Self {
+            failure,
+            detail: detail.to_string(),
+        }
+    }
+}
+
+/// Maps an error of one phase of the request to its failure
+fn fail<E: fmt::Display>(failure: Failure) -> impl FnOnce(E) -> ProbeError {
+    move |e| ProbeError::new(failure, e)
+}
+
+/// The final response of a probe, kept with the incident it opened
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Snapshot {
+    pub remote_ip: String,
+    /// e.g. `HTTP/1.1 503 Service Unavailable`
+    pub status_line: String,
+    /// One `name: value` per line, with the credentials redacted
+    pub headers: String,
+    /// The start of the body as lossy UTF-8
+    pub body: String,
+    /// The body is longer than `SNAPSHOT_BODY_SIZE`
+    pub truncated: bool,
+}
+
+impl Snapshot {
+    fn new<B>(addr: SocketAddr, response: &hyper::Response<B>) -> Self {
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                    "[redacted]".into()
+                } else {
+                    String::from_utf8_lossy(value.as_bytes())
+                };
+                format!("{}: {}", name, value)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Self {
+            remote_ip: addr.ip().to_string(),
+            status_line: format!("{:?} {}", response.version(), response.status()),
+            headers,
+            body: String::new(),
+            truncated: false,
+        }
+    }
+
+    fn set_body(&mut self, body: &[u8], max_size: usize) {
+        self.truncated = body.len() > max_size;
+        self.body = String::from_utf8_lossy(&body[..body.len().min(max_size)]).into_owned();
+    }
+}
+
+/// What a probe saw, the timings and the snapshot of the last request are
+/// kept when it fails or times out
+#[derive(Debug)]
+pub struct Probe {
+    pub result: Result<StatusCode, ProbeError>,
+    /// Milliseconds until the head of the final response, redirects included
+    pub latency: Option<i64>,
+    pub timings: Timings,
+    /// `None` when no final response was received
+    pub snapshot: Option<Snapshot>,
+}
+
+#[derive(Default)]
+struct Capture {
+    timings: Timings,
+    snapshot: Option<Snapshot>,
+    /// When the response head was received
+    head_at: Option<Instant>,
+}
+
+/// Rate limited servers are still up
+pub fn is_up(status: StatusCode) -> bool {
+    status.is_success() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Sends a GET request to the URL, following redirects, and returns the
+/// final status. Every phase gets its own span and the request carries the
+/// `traceparent` of the current one
+pub async fn get(url: &str, timeout: Duration) -> Probe {
+    get_via(url, timeout, &PROXIES).await
+}
+
+async fn get_via(url: &str, timeout: Duration, proxies: &Matcher) -> Probe {
+    let start = Instant::now();
+    let mut capture = Capture::default();
+
+    let result = tokio::time::timeout(timeout, follow(url, timeout, proxies, &mut capture))
+        .await
+        .unwrap_or_else(|_| {
+            let detail = format!("No response after {}s", timeout.as_secs());
+            Err(ProbeError::new(Failure::Timeout, detail))
+        });
+
+    Probe {
+        result,
+        latency: capture.head_at.map(|at| (at - start).as_millis() as i64),
+        timings: capture.timings,
+        snapshot: capture.snapshot,
+    }
+}
+
+async fn follow(
+    url: &str,
+    timeout: Duration,
+    proxies: &Matcher,
+    capture: &mut Capture,
+) -> Result<StatusCode, ProbeError> {
+    let mut url = Url::parse(url).map_err(fail(Failure::Connection))?;
+
+    for _ in 0..=MAX_REDIRECTS {
+        *capture = Capture::default();
+        let (status, location) = request(&url, timeout, proxies, capture).await?;
+
+        match location {
+            Some(location) if status.is_redirection() => {
+                url = url.join(&location).map_err(fail(Failure::Http))?;
+            }
+            _ => return Ok(status),
+        }
+    }
+
+    Err(ProbeError::new(Failure::Http, "Too many redirects"))
+}
+
+async fn request(
+    url: &Url,
+    timeout: Duration,
+    proxies: &Matcher,
+    capture: &mut Capture,
+) -> Result<(StatusCode, Option<String>), ProbeError> {
+    // The fragment is never sent
+    let uri = url
+        .as_str()
+        .split('#')
+        .next()
+        .unwrap_or_default()
+        .parse::<Uri>()
+        .map_err(fail(Failure::Connection))?;
+    let (host, port) = host_and_port(&uri)?;
+    let proxy = proxies.intercept(&uri);
+
+    // The connection goes to the proxy when there's one
+    let (connect_host, connect_port) = match &proxy {
+        Some(proxy) => host_and_port(proxy.uri())?,
+        None => (host, port),
+    };
+
+    let start = Instant::now();
+    let addrs = resolve(connect_host, connect_port)
+        .instrument(info_span!("dns", host = connect_host))
+        .await?;
+    capture.timings.dns = elapsed(start);
+
+    let start = Instant::now();
+    let (mut stream, addr) = connect(&addrs, timeout).await?;
+
+    let mut absolute_form = false;
+    let mut proxy_auth = None;
+    if let Some(proxy) = &proxy {
+        stream = connect_proxy(stream, proxy, connect_host).await?;
+
+        match url.scheme() {
+            "https" => {
+                let authority = format!("{}:{}", host, port);
+                stream = tunnel(stream, &authority, proxy.basic_auth())
+                    .instrument(info_span!("tunnel", %authority))
+                    .await?;
+            }
+            // Plain HTTP requests are forwarded by the proxy
+            _ => {
+                absolute_form = true;
+                proxy_auth = proxy.basic_auth().cloned();
+            }
+        }
+    }
+    capture.timings.connect = elapsed(start);
+
+    match url.scheme() {
+        "https" => {
+            let start = Instant::now();
+            let stream = TLS_CONNECTOR
+                .connect(host, stream)
+                .instrument(info_span!("tls"))
+                .await
+                .map_err(fail(Failure::Tls))?;
+            capture.timings.tls = elapsed(start);
+
+            let version = match stream.get_ref().negotiated_alpn() {
+                Ok(Some(protocol)) if protocol == b"h2" => Version::HTTP_2,
+                _ => Version::HTTP_11,
+            };
+            let connection = Connection {
+                stream: Box::new(stream),
+                addr,
+                version,
+                absolute_form: false,
+                proxy_auth: None,
+            };
+
+            send(connection, &uri, capture).await
+        }
+        "http" => {
+            let connection = Connection {
+                stream,
+                addr,
+                version: Version::HTTP_11,
+                absolute_form,
+                proxy_auth,
+            };
+
+            send(connection, &uri, capture).await
+        }
+        scheme => Err(ProbeError::new(
+            Failure::Connection,
+            format!("Unsupported scheme {}", scheme),
+        )),
+    }
+}
+
+fn host_and_port(uri: &Uri) -> Result<(&str, u16), ProbeError> {
+    let host = uri
+        .host()
+        .ok_or_else(|| ProbeError::new(Failure::Connection, format!("{} has no host", uri)))?;
+    let port = match (uri.port_u16(), uri.scheme_str()) {
+        (Some(port), _) => port,
+        (None, Some("https")) => 443,
+        (None, Some("http")) => 80,
+        _ => {
+            let detail = format!("{} has no port", uri);
+            return Err(ProbeError::new(Failure::Connection, detail));
+        }
+    };
+
+    // IPv6 addresses are bracketed in URLs
+    Ok((host.trim_start_matches('[').trim_end_matches(']'), port))
+}
+
+async fn resolve(host: &str, port: u16) -> Result<Vec<SocketAddr>, ProbeError> {
+    let addrs = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(fail(Failure::Dns))?
+        .collect::<Vec<_>>();
+
+    if addrs.is_empty() {
+        let detail = format!("{} has no addresses", host);
+        return Err(ProbeError::new(Failure::Dns, detail));
+    }
+
+    Ok(addrs)
+}
+
+/// Tries the addresses in order until one accepts the connection, each one
+/// gets an equal share of the timeout so an unreachable address doesn't use
+/// it all
+async fn connect(
+    addrs: &[SocketAddr],
+    timeout: Duration,
+) -> Result<(Box<dyn Io>, SocketAddr), ProbeError> {
+    let attempt_timeout = timeout / addrs.len() as u32;
+    let mut last_error = None;
+
+    for &addr in addrs {
+        let attempt = tokio::time::timeout(attempt_timeout, TcpStream::connect(addr))
+            .instrument(info_span!("connect", %addr))
+            .await
+            .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()));
+
+        match attempt {
+            Ok(tcp) => return Ok((Box::new(tcp), addr)),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    let e = last_error.unwrap_or_else(|| io::ErrorKind::NotFound.into());
+    Err(match e.kind() {
+        io::ErrorKind::ConnectionRefused => ProbeError::new(Failure::ConnectionRefused, e),
+        _ => ProbeError::new(Failure::Connection, e),
+    })
+}
+
+/// Wraps the connection to an HTTPS proxy in TLS
+async fn connect_proxy(
+    stream: Box<dyn Io>,
+    proxy: &Intercept,
+    host: &str,
+) -> Result<Box<dyn Io>, ProbeError> {
+    match proxy.uri().scheme_str() {
+        Some("http") => Ok(stream),
+        Some("https") => {
+            let stream = PROXY_TLS_CONNECTOR
+                .connect(host, stream)
+                .await
+                .map_err(fail(Failure::Connection))?;
+            Ok(Box::new(stream))
+        }
+        scheme => Err(ProbeError::new(
+            Failure::Connection,
+            format!("Unsupported proxy scheme {}", scheme.unwrap_or_default()),
+        )),
+    }
+}
+
+/// Opens a tunnel to `authority` through the proxy with `CONNECT`
+async fn tunnel(
+    stream: Box<dyn Io>,
+    authority: &str,
+    auth: Option<&HeaderValue>,
+) -> Result<Box<dyn Io>, ProbeError> {
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(fail(Failure::Connection))?;
+    tokio::spawn(async move {
+        let _ = connection.with_upgrades().await;
+    });
+
+    let mut request = Request::connect(authority)
+        .header(HOST, authority)
+        .body(Empty::<Bytes>::new())
+        .map_err(fail(Failure::Connection))?;
+    if let Some(auth) = auth {
+        request
+            .headers_mut()
+            .insert(PROXY_AUTHORIZATION, auth.clone());
+    }
+
+    let response = sender
+        .send_request(request)
+        .await
+        .map_err(fail(Failure::Connection))?;
+    if !response.status().is_success() {
+        let detail = format!("The proxy refused the tunnel with {}", response.status());
+        return Err(ProbeError::new(Failure::Connection, detail));
+    }
+
+    let upgraded = hyper::upgrade::on(response)
+        .await
+        .map_err(fail(Failure::Connection))?;
+
+    Ok(Box::new(TokioIo::new(upgraded)))
+}
+
+/// An established connection to send the request over
+struct Connection {
+    stream: Box<dyn Io>,
+    addr: SocketAddr,
+    version: Version,
+    /// Plain HTTP requests through a proxy are sent with the absolute URL
+    absolute_form: bool,
+    proxy_auth: Option<HeaderValue>,
+}
+
+async fn send(
+    connection: Connection,
+    uri: &Uri,
+    capture: &mut Capture,
+) -> Result<(StatusCode, Option<String>), ProbeError> {
+    let Connection {
+        stream,
+        addr,
+        version,
+        absolute_form,
+        proxy_auth,
+    } = connection;
+
+    let response = async {
+        let start = Instant::now();
+
+        let mut request = Request::get(uri)
+            .header(USER_AGENT, PROBE_USER_AGENT)
+            .header(ACCEPT, "*/*")
+            .body(Empty::<Bytes>::new())
+            .map_err(fail(Failure::Connection))?;
+        inject_trace_context(request.headers_mut());
+
+        let response = if version == Version::HTTP_2 {
+            // HTTP/2 takes the scheme and authority from the full URI
+            *request.version_mut() = Version::HTTP_2;
+            let (mut sender, connection) =
+                hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                    .await
+                    .map_err(fail(Failure::Connection))?;
+            tokio::spawn(async move {
+                let _ = connection.await;
+            });
+
+            sender.send_request(request).await
+        } else {
+            let host = match uri.port() {
+                Some(port) => format!("{}:{}", uri.host().unwrap_or_default(), port),
+                None => uri.host().unwrap_or_default().to_string(),
+            };
+            let headers = request.headers_mut();
+            headers.insert(
+                HOST,
+                HeaderValue::from_str(&host).map_err(fail(Failure::Connection))?,
+            );
+            headers.insert(CONNECTION, HeaderValue::from_static("close"));
+            if let Some(auth) = proxy_auth {
+                headers.insert(PROXY_AUTHORIZATION, auth);
+            }
+            if !absolute_form {
+                let path = uri.path_and_query().map_or("/", |path| path.as_str());
+                *request.uri_mut() = path.parse().map_err(fail(Failure::Connection))?;
+            }
+
+            let (mut sender, connection) =
+                hyper::client::conn::http1::handshake(TokioIo::new(stream))
+                    .await
+                    .map_err(fail(Failure::Connection))?;
+            tokio::spawn(async move {
+                let _ = connection.await;
+            });
+
+            sender.send_request(request).await
+        }
+        .map_err(fail(Failure::Connection))?;
+        capture.timings.ttfb = elapsed(start);
+        capture.head_at = Some(Instant::now());
+
+        Ok(response)
+    }
+    .instrument(info_span!("ttfb"))
+    .await?;
+
+    let status = response.status();
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .map(str::to_string);
+
+    if status.is_redirection() && location.is_some() {
+        return Ok((status, location));
+    }
+
+    let snapshot = capture.snapshot.insert(Snapshot::new(addr, &response));
+
+    // No need to download it when the `Content-Length` is already too large
+    let max_size = get_max_body_size();
+    let mut body = response.into_body();
+    if body.size_hint().lower() > max_size as u64 {
+        let detail = format!("The body is larger than {} bytes", max_size);
+        return Err(ProbeError::new(Failure::BodyTooLarge, detail));
+    }
+
+    // Only failing responses are kept, one more byte tells if it's truncated
+    if !is_up(status) {
+        let snapshot_size = get_snapshot_body_size();
+        let mut bytes = Vec::new();
+
+        async {
+            while bytes.len() <= snapshot_size {
+                match body.frame().await {
+                    Some(Ok(frame)) => {
+                        if let Some(data) = frame.data_ref() {
+                            bytes.extend_from_slice(data);
+                        }
+                    }
+                    _ => break,
+                }
+            }
+        }
+        .instrument(info_span!("body"))
+        .await;
+        snapshot.set_body(&bytes, snapshot_size);
+    }
+
+    Ok((status, location))
+}
+
+/// Adds the `traceparent` of the current span so the probe can be found
+/// next to the server side traces
+fn inject_trace_context(headers: &mut HeaderMap) {
+    let context = Span::current().context();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(headers))
+    });
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    #[tokio::test]
+    async fn test_get_follows_redirects() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buffer = [0; 1024];
+                let read = stream.read(&mut buffer).await.unwrap();
+                let request = String::from_utf8_lossy(&buffer[..read]);
+
+                let response = if request.starts_with("GET /old ") {
+                    "HTTP/1.1 301 Moved Permanently\r\nLocation: /new?a=1\r\nContent-Length: 0\r\n\r\n"
+                } else if request.starts_with("GET /new?a=1 ") {
+                    "HTTP/1.1 204 No Content\r\n\r\n"
+                } else if request.starts_with("GET /large ") {
+                    "HTTP/1.1 200 OK\r\nContent-Length: 20000000\r\n\r\n"
+                } else {
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"
+                };
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let url = format!("http://{}/old", addr);
+        let probe = get(&url, Duration::from_secs(5)).await;
+        let timings = probe.timings;
+
+        assert_eq!(probe.result.unwrap(), StatusCode::NO_CONTENT);
+        assert!(timings.dns.is_some() && timings.connect.is_some() && timings.ttfb.is_some());
+        assert_eq!(timings.tls, None);
+
+        let url = format!("http://{}/large", addr);
+        let probe = get(&url, Duration::from_secs(5)).await;
+
+        assert_eq!(probe.result.unwrap_err().failure, Failure::BodyTooLarge);
+        assert_eq!(probe.snapshot.unwrap().status_line, "HTTP/1.1 200 OK");
+    }
+
+    #[tokio::test]
+    async fn test_get_connection_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let url = format!("http://{}/", addr);
+        let probe = get(&url, Duration::from_secs(5)).await;
+
+        assert_eq!(
+            probe.result.unwrap_err().failure,
+            Failure::ConnectionRefused
+        );
+        assert!(probe.timings.dns.is_some() && probe.timings.connect.is_none());
+        assert!(probe.snapshot.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_snapshot() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0; 1024];
+            let _ = stream.read(&mut buffer).await.unwrap();
+            let response = "HTTP/1.1 503 Service Unavailable\r\nSet-Cookie: session=secret\r\nContent-Length: 11\r\n\r\nMaintenance";
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let url = format!("http://{}/", addr);
+        let probe = get(&url, Duration::from_secs(5)).await;
+        let snapshot = probe.snapshot.unwrap();
+
+        assert_eq!(probe.result.unwrap(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(snapshot.remote_ip, "127.0.0.1");
+        assert_eq!(snapshot.status_line, "HTTP/1.1 503 Service Unavailable");
+        assert!(snapshot.headers.contains("set-cookie: [redacted]"));
+        assert!(!snapshot.headers.contains("secret"));
+        assert_eq!(snapshot.body, "Maintenance");
+        assert!(!snapshot.truncated);
+    }
+
+    #[tokio::test]
+    async fn test_get_skips_the_body_when_up() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0; 1024];
+            let _ = stream.read(&mut buffer).await.unwrap();
+            let response = "HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\n";
+            stream.write_all(response.as_bytes()).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            stream.write_all(b"Slow answer").await.unwrap();
+        });
+
+        let url = format!("http://{}/", addr);
+        let start = Instant::now();
+        let probe = get(&url, Duration::from_secs(5)).await;
+
+        assert_eq!(probe.result.unwrap(), StatusCode::OK);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(probe.latency.unwrap() < 5000);
+        assert_eq!(probe.snapshot.unwrap().body, "");
+    }
+
+    #[tokio::test]
+    async fn test_connect_tries_every_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let refused = closed.local_addr().unwrap();
+        drop(closed);
+
+        let (_, connected) = connect(&[refused, addr], Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(connected, addr);
+
+        let error = connect(&[refused], Duration::from_secs(5))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.failure, Failure::ConnectionRefused);
+    }
+
+    #[tokio::test]
+    async fn test_get_through_a_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let proxy = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0; 1024];
+            let read = stream.read(&mut buffer).await.unwrap();
+            let response = "HTTP/1.1 204 No Content\r\n\r\n";
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&buffer[..read]).into_owned()
+        });
+
+        let proxies = Matcher::builder()
+            .http(format!("http://user:pass@{}", addr))
+            .build();
+        let probe = get_via(
+            "http://example.invalid/path?a=1",
+            Duration::from_secs(5),
+            &proxies,
+        )
+        .await;
+        let request = proxy.await.unwrap();
+
+        assert_eq!(probe.result.unwrap(), StatusCode::NO_CONTENT);
+        assert!(request.starts_with("GET http://example.invalid/path?a=1 HTTP/1.1\r\n"));
+        assert!(request.contains("proxy-authorization: Basic dXNlcjpwYXNz\r\n"));
+        assert_eq!(probe.snapshot.unwrap().remote_ip, "127.0.0.1");
+    }
+
+    #[tokio::test]
+    async fn test_tunnel() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0; 1024];
+            let read = stream.read(&mut buffer).await.unwrap();
+            assert!(buffer[..read].starts_with(b"CONNECT example.invalid:443 HTTP/1.1\r\n"));
+            let response = "HTTP/1.1 200 Connection Established\r\n\r\n";
+            stream.write_all(response.as_bytes()).await.unwrap();
+
+            // Echoes what goes through the tunnel
+            let read = stream.read(&mut buffer).await.unwrap();
+            stream.write_all(&buffer[..read]).await.unwrap();
+        });
+
+        let (stream, _) = connect(&[addr], Duration::from_secs(5)).await.unwrap();
+        let mut stream = tunnel(stream, "example.invalid:443", None).await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        let mut buffer = [0; 5];
+        stream.read_exact(&mut buffer).await.unwrap();
+
+        assert_eq!(&buffer, b"hello");
+    }
+
+    #[test]
+    fn test_timings_display() {
+        let timings = Timings {
+            dns: Some(12),
+            connect: Some(30),
+            tls: None,
+            ttfb: Some(210),
+        };
+
+        assert_eq!(timings.to_string(), "DNS 12ms, connect 30ms, TTFB 210ms");
+        assert!(!timings.is_empty());
+        assert!(Timings::default().is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+use chrono::Local;
+use tracing::info;
+
+use crate::{
+    constants::{get_location, get_quorum},
+    db::{endpoint::Lookup, location_check::LocationCheck, url::Url, Db},
+    policy::CheckPolicy,
+};
+
+/// Stores the monitor's own check along with the agents' ones and decides the
+/// outcome from every location that reported recently. A location is left
+/// out once it hasn't reported for twice the endpoint's longest interval
+pub async fn apply(url: &Url, lookup: &mut Lookup, db: &Db) -> anyhow::Result<()> {
+    // Heartbeats are pushed to the monitor, there's nothing to compare
+    if url.is_heartbeat() {
+        return Ok(());
+    }
+
+    record(url, lookup, db).await?;
+
+    let now = Local::now().naive_local();
+    let window = CheckPolicy::from_url(url)?.longest_interval() * 2;
+    let since = now - chrono::Duration::from_std(window)?;
+    let checks = db.location_check.get_since(url.as_str(), since).await?;
+
+    // The monitor is the only location
+    if checks.len() < 2 {
+        return Ok(());
+    }
+
+    let failing = checks
+        .iter()
+        .filter(|check| !check.success)
+        .collect::<Vec<_>>();
+    let is_down = reaches_quorum(failing.len(), checks.len(), get_quorum());
+
+    if is_down && lookup.is_success {
+        // The cause is taken from a location that saw the failure
+        let first = failing[0];
+        lookup.failure = first.failure;
+        lookup.detail = first.failure_detail.clone();
+    } else if !is_down && !lookup.is_success {
+        info!(
+            failing = failing.len(),
+            locations = checks.len(),
+            "The failure wasn't confirmed by the other locations"
+        );
+        lookup.failure = None;
+        lookup.detail = None;
+        lookup.snapshot = None;
+    }
+
+    lookup.is_success = !is_down;
+    lookup.failing_locations = failing.iter().map(|check| check.location.clone()).collect();
+
+    Ok(())
+}
+
+/// Stores the check as this instance's location, a standby only records its
+/// checks and the leader counts them like an agent's
+pub async fn record(url: &Url, lookup: &Lookup, db: &Db) -> anyhow::Result<()> {
+    if url.is_heartbeat() {
+        return Ok(());
+    }
+
+    db.location_check
+        .set(&LocationCheck {
+            url: url.as_str().to_string(),
+            location: get_location(),
+            success: lookup.is_success,
+            latency: lookup.latency,
+            failure: lookup.failure,
+            failure_detail: lookup.detail.clone(),
+            checked_at: Local::now().naive_local(),
+        })
+        .await
+}
+
+/// A majority of the locations has to fail unless `quorum` is set, it can't
+/// require more locations than the ones reporting
+fn reaches_quorum(failing: usize, locations: usize, quorum: Option<usize>) -> bool {
+    let needed = quorum.unwrap_or(locations / 2 + 1).min(locations);
+
+    failing >= needed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::endpoint::Failure;
+
+    async fn setup() -> (Url, Db) {
+        let url = Url::from("https://example.com#interval=60000".to_string());
+        let db = Db::in_memory(std::slice::from_ref(&url)).await.unwrap();
+
+        (url, db)
+    }
+
+    async fn report(db: &Db, url: &Url, location: &str, success: bool, minutes_ago: i64) {
+        db.location_check
+            .set(&LocationCheck {
+                url: url.as_str().to_string(),
+                location: location.to_string(),
+                success,
+                latency: Some(100),
+                failure: (!success).then_some(Failure::Timeout),
+                failure_detail: (!success).then(|| "No response after 10s".to_string()),
+                checked_at: Local::now().naive_local() - chrono::Duration::minutes(minutes_ago),
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_apply_takes_the_cause_from_the_failing_locations() {
+        let (url, db) = setup().await;
+        report(&db, &url, "eu", false, 0).await;
+        report(&db, &url, "us", false, 0).await;
+
+        let mut lookup = Lookup {
+            is_success: true,
+            ..Default::default()
+        };
+        apply(&url, &mut lookup, &db).await.unwrap();
+
+        assert!(!lookup.is_success);
+        assert_eq!(lookup.failure, Some(Failure::Timeout));
+        assert_eq!(lookup.failing_locations, vec!["eu", "us"]);
+    }
+
+    #[tokio::test]
+    async fn test_apply_ignores_stale_locations() {
+        let (url, db) = setup().await;
+        report(&db, &url, "eu", false, 0).await;
+        report(&db, &url, "us", false, 3).await;
+
+        let mut lookup = Lookup {
+            is_success: true,
+            ..Default::default()
+        };
+        apply(&url, &mut lookup, &db).await.unwrap();
+
+        // The monitor and eu are left, a tie isn't a majority
+        assert!(lookup.is_success);
+        assert_eq!(lookup.failure, None);
+        assert_eq!(lookup.failing_locations, vec!["eu"]);
+    }
+
+    #[tokio::test]
+    async fn test_apply_overrules_an_unconfirmed_failure() {
+        let (url, db) = setup().await;
+        report(&db, &url, "eu", true, 0).await;
+        report(&db, &url, "us", true, 0).await;
+
+        let mut lookup = Lookup {
+            failure: Some(Failure::Connection),
+            detail: Some("Connection reset".to_string()),
+            ..Default::default()
+        };
+        apply(&url, &mut lookup, &db).await.unwrap();
+
+        assert!(lookup.is_success);
+        assert_eq!(lookup.failure, None);
+        assert_eq!(lookup.detail, None);
+        assert_eq!(lookup.failing_locations, vec![get_location()]);
+    }
+
+    #[test]
+    fn test_reaches_quorum_with_a_majority_by_default() {
+        assert!(!reaches_quorum(1, 3, None));
+        assert!(reaches_quorum(2, 3, None));
+        assert!(!reaches_quorum(1, 2, None));
+        assert!(reaches_quorum(2, 2, None));
+        assert!(reaches_quorum(1, 1, None));
+    }
+
+    #[test]
+    fn test_reaches_quorum_capped_by_the_locations() {
+        assert!(reaches_quorum(1, 3, Some(1)));
+        assert!(!reaches_quorum(2, 3, Some(3)));
+        assert!(reaches_quorum(2, 2, Some(3)));
+        assert!(!reaches_quorum(0, 2, Some(3)));
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{health::HEALTH, tasks::TASKS};
+use std::{cmp::Reverse, collections::BinaryHeap, future::Future, sync::Arc, time::Duration};
+use tokio::{
+    sync::{mpsc, watch, Semaphore},
+    time::Instant,
+};
+
+const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Runs the checks of every endpoint from a single loop, each endpoint has
+/// its own interval and the checks run at a fixed rate no matter how long
+/// they take.
+pub struct Scheduler {
+    intervals: Vec<Duration>,
+    max_concurrent: usize,
+}
+
+impl Scheduler {
+    /// `intervals` has the initial interval of every endpoint,
+    /// `max_concurrent` caps how many checks run at the same time
+    pub fn new(intervals: Vec<Duration>, max_concurrent: usize) -> Self {
+        Self {
+            intervals,
+            max_concurrent,
+        }
+    }
+
+    /// Calls `job` with the index of the endpoint whenever its check is due,
+    /// the job returns the interval until the endpoint's next check.
+    ///
+    /// Once `shutdown` is `true` no new checks start, the checks waiting for
+    /// a slot are dropped and it returns when the running ones are done.
+    pub async fn run<F, Fut>(self, job: F, mut shutdown: watch::Receiver<bool>)
+    where
+        F: Fn(usize) -> Fut,
+        Fut: Future<Output = Duration> + Send + 'static,
+    {
+        let semaphore = Arc::new(Semaphore::new(self.max_concurrent));
+        let (done_tx, mut done_rx) = mpsc::unbounded_channel();
+
+        let start = Instant::now();
+        let mut queue = spread(&self.intervals)
+            .into_iter()
+            .enumerate()
+            .map(|(i, offset)| Reverse((start + offset, i)))
+            .collect::<BinaryHeap<_>>();
+
+        let mut in_flight = 0;
+        let stopping = shutdown.clone();
+
+        loop {
+            let due = queue.peek().map(|Reverse((scheduled, _))| *scheduled);
+
+            tokio::select! {
+                // An endpoint is only queued again once its check is done so
+                // checks of the same endpoint never overlap
+                Some((i, scheduled, interval)) = done_rx.recv() => {
+                    in_flight -= 1;
+                    let next = next_run(scheduled, interval, Instant::now());
+                    queue.push(Reverse((next, i)));
+                }
+                _ = shutdown.wait_for(|stop| *stop) => break,
+                _ = tokio::time::sleep_until(due.unwrap_or_else(Instant::now)), if due.is_some() => {
+                    let Some(Reverse((scheduled, i))) = queue.pop() else {
+                        continue;
+                    };
+
+                    let check = job(i);
+                    let semaphore = Arc::clone(&semaphore);
+                    let done_tx = done_tx.clone();
+                    let stopping = stopping.clone();
+                    in_flight += 1;
+
+                    // Tracked so the shutdown waits for it along with the
+                    // pings and the crons
+                    TASKS.spawn(async move {
+                        let permit = semaphore.acquire_owned().await;
+                        if *stopping.borrow() {
+                            done_tx.send((i, scheduled, Duration::ZERO)).ok();
+                            return;
+                        }
+
+                        HEALTH.record_check_started(Instant::now().saturating_duration_since(scheduled));
+                        let interval = check.await;
+                        drop(permit);
+
+                        done_tx.send((i, scheduled, interval)).ok();
+                    });
+                }
+            }
+        }
+
+        while in_flight > 0 && done_rx.recv().await.is_some() {
+            in_flight -= 1;
+        }
+    }
+}
+
+/// Spreads the first checks over their intervals so they don't all start
+/// at once
+fn spread(intervals: &[Duration]) -> Vec<Duration> {
+    let count = intervals.len() as u32;
+
+    intervals
+        .iter()
+        .enumerate()
+        .map(|(i, interval)| *interval * i as u32 / count)
+        .collect()
+}
+
+/// The next run is one interval after the scheduled one, runs that were
+/// missed because a check took longer than its interval are skipped
+fn next_run(scheduled: Instant, interval: Duration, now: Instant) -> Instant {
+    // The intervals are validated, a zero one would never catch up with `now`
+    let interval = interval.max(MIN_INTERVAL);
+    let mut next = scheduled + interval;
+
+    while next <= now {
+        next += interval;
+    }
+
+    next
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    type Runs = Arc<Mutex<Vec<(usize, u128)>>>;
+
+    /// Runs the scheduler for `duration` and returns when every check ran
+    async fn run_for<F>(scheduler: Scheduler, duration: Duration, interval: F) -> Runs
+    where
+        F: Fn(usize, usize) -> Duration + Send + Sync + 'static,
+    {
+        let runs: Runs = Arc::new(Mutex::new(Vec::new()));
+        let interval = Arc::new(interval);
+        let start = Instant::now();
+
+        let (shutdown_tx, shutdown) = watch::channel(false);
+        let job_runs = Arc::clone(&runs);
+        let handle = tokio::spawn(scheduler.run(
+            move |i| {
+                let runs = Arc::clone(&job_runs);
+                let interval = Arc::clone(&interval);
+
+                async move {
+                    let count = {
+                        let mut runs = runs.lock().unwrap();
+                        runs.push((i, start.elapsed().as_millis()));
+                        runs.iter().filter(|(index, _)| *index == i).count()
+                    };
+
+                    // Slow checks don't push the next runs back
+                    tokio::time::sleep(Duration::from_millis(30)).await;
+                    interval(i, count)
+                }
+            },
+            shutdown,
+        ));
+
+        tokio::time::sleep(duration).await;
+        shutdown_tx.send_replace(true);
+        handle.await.unwrap();
+
+        runs
+    }
+
+    fn times(runs: &Runs, index: usize) -> Vec<u128> {
+        runs.lock()
+            .unwrap()
+            .iter()
+            .filter(|(i, _)| *i == index)
+            .map(|(_, time)| *time)
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fixed_rate() {
+        let intervals = vec![Duration::from_millis(100), Duration::from_millis(250)];
+        let scheduler = Scheduler::new(intervals.clone(), 1);
+
+        let runs = run_for(scheduler, Duration::from_millis(990), move |i, _| {
+            intervals[i]
+        })
+        .await;
+
+        // Only one check runs at a time so the checks wait for each other,
+        // the delays don't add up
+        assert_eq!(
+            times(&runs, 0),
+            vec![0, 100, 200, 300, 405, 500, 600, 700, 800, 905]
+        );
+        // Starts half an interval later
+        assert_eq!(times(&runs, 1), vec![130, 375, 630, 875]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_changing_interval() {
+        let scheduler = Scheduler::new(vec![Duration::from_millis(100)], 1);
+
+        // Checks every 20ms after the third check, the checks take 30ms so
+        // every other run is skipped
+        let runs = run_for(scheduler, Duration::from_millis(500), |_, count| {
+            if count < 3 {
+                Duration::from_millis(100)
+            } else {
+                Duration::from_millis(20)
+            }
+        })
+        .await;
+
+        assert_eq!(
+            times(&runs, 0),
+            vec![0, 100, 200, 240, 280, 320, 360, 400, 440, 480]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown() {
+        let scheduler = Scheduler::new(vec![Duration::from_millis(100); 2], 1);
+        let (shutdown_tx, shutdown) = watch::channel(false);
+        let done = Arc::new(Mutex::new(Vec::new()));
+        let start = Instant::now();
+
+        let job_done = Arc::clone(&done);
+        let handle = tokio::spawn(scheduler.run(
+            move |i| {
+                let done = Arc::clone(&job_done);
+
+                async move {
+                    tokio::time::sleep(Duration::from_millis(80)).await;
+                    done.lock().unwrap().push(i);
+                    Duration::from_millis(100)
+                }
+            },
+            shutdown,
+        ));
+
+        // The second check is due at 50ms and waits for the first one
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        shutdown_tx.send_replace(true);
+        handle.await.unwrap();
+
+        // The running check finished, the waiting one never started
+        assert_eq!(*done.lock().unwrap(), vec![0]);
+        assert_eq!(start.elapsed(), Duration::from_millis(80));
+    }
+
+    #[test]
+    fn test_next_run() {
+        let now = Instant::now();
+        let interval = Duration::from_secs(10);
+
+        assert_eq!(next_run(now, interval, now), now + interval);
+        assert_eq!(
+            next_run(now, interval, now + Duration::from_secs(25)),
+            now + Duration::from_secs(30)
+        );
+        assert_eq!(
+            next_run(now, Duration::ZERO, now + Duration::from_millis(5)),
+            now + Duration::from_millis(6)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_zero_interval() {
+        let scheduler = Scheduler::new(vec![Duration::from_millis(100)], 1);
+
+        // Runs again as soon as the check is done instead of hanging
+        let runs = run_for(scheduler, Duration::from_millis(100), |_, _| Duration::ZERO).await;
+
+        assert_eq!(times(&runs, 0), vec![0, 31, 62, 93]);
+    }
+}
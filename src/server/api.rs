@@ -0,0 +1,853 @@
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Arc};
+use subtle::ConstantTimeEq;
+use tracing::error;
+
+use super::{events, AppState};
+use crate::{
+    agent::AgentCheck,
+    constants::get_location,
+    db::{
+        check_result::CheckResult,
+        endpoint::Endpoint,
+        incident::{Incident, IncidentFilter},
+        location_check::LocationCheck,
+        url::Url,
+    },
+    dependency,
+    dns::DnsCheck,
+    heartbeat::HeartbeatCheck,
+    metrics::METRICS,
+    policy::CheckPolicy,
+    probe::Snapshot,
+};
+
+const DEFAULT_CHECKS_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 1000;
+
+/// Every route requires one of `tokens`, see [`authorize`]
+pub fn router(tokens: Vec<String>) -> Router<AppState> {
+    let tokens = Arc::new(tokens);
+    let events = Router::new()
+        .route("/events", get(events::events))
+        .route_layer(middleware::from_fn_with_state(
+            Arc::clone(&tokens),
+            authorize_events,
+        ));
+
+    Router::new()
+        .route("/endpoints", get(list_endpoints).post(create_endpoint))
+        .route(
+            "/endpoints/{id}",
+            get(get_endpoint)
+                .put(update_endpoint)
+                .delete(delete_endpoint),
+        )
+        .route("/incidents", get(list_incidents))
+        .route("/incidents/{id}/snapshot", get(get_incident_snapshot))
+        .route("/agent/endpoints", get(list_agent_endpoints))
+        .route("/agent/checks", post(add_agent_check))
+        .route_layer(middleware::from_fn_with_state(tokens, authorize))
+        .merge(events)
+}
+
+/// Requires one of the `API_TOKENS` as a bearer token, the API is disabled
+/// when no tokens are set
+async fn authorize(
+    State(tokens): State<Arc<Vec<String>>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    check_token(&tokens, bearer_token(&request))?;
+
+    Ok(next.run(request).await)
+}
+
+/// Browsers can't set headers on an `EventSource` so the event stream also
+/// accepts the token as `?token=`. The other routes don't, URLs end up in
+/// access logs and `Referer` headers
+async fn authorize_events(
+    State(tokens): State<Arc<Vec<String>>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let query =
+        Query::<TokenQuery>::try_from_uri(request.uri()).map_err(|_| ApiError::Unauthorized)?;
+    check_token(&tokens, bearer_token(&request).or(query.token.as_deref()))?;
+
+    Ok(next.run(request).await)
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+fn bearer_token(request: &Request) -> Option<&str> {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+fn check_token(tokens: &[String], token: Option<&str>) -> Result<(), ApiError> {
+    if tokens.is_empty() {
+        return Err(ApiError::NotFound);
+    }
+
+    match token {
+        Some(token) if is_valid_token(tokens, token) => Ok(()),
+        _ => Err(ApiError::Unauthorized),
+    }
+}
+
+/// Compares the token with every configured one in constant time so the
+/// response time doesn't reveal how much of a token matched
+fn is_valid_token(tokens: &[String], token: &str) -> bool {
+    tokens.iter().fold(false, |is_valid, t| {
+        is_valid | bool::from(t.as_bytes().ct_eq(token.as_bytes()))
+    })
+}
+
+fn is_unique_violation(e: &anyhow::Error) -> bool {
+    match e.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::Database(e)) => e.is_unique_violation(),
+        _ => false,
+    }
+}
+
+pub enum ApiError {
+    Unauthorized,
+    NotFound,
+    BadRequest(String),
+    Conflict(String),
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Internal(e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            Self::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized".to_string()),
+            Self::NotFound => (StatusCode::NOT_FOUND, "Not found".to_string()),
+            Self::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            Self::Conflict(message) => (StatusCode::CONFLICT, message),
+            Self::Internal(e) => {
+                METRICS.record_error(&e);
+                error!(error = %e, "API request failed");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal server error".to_string(),
+                )
+            }
+        };
+
+        (status, Json(ErrorResponse { error: message })).into_response()
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+#[derive(Serialize)]
+struct EndpointResponse {
+    name: String,
+    tags: Vec<String>,
+    #[serde(flatten)]
+    endpoint: Endpoint,
+}
+
+impl From<Endpoint> for EndpointResponse {
+    fn from(endpoint: Endpoint) -> Self {
+        let url = endpoint.configured_url();
+
+        Self {
+            name: url.name().to_string(),
+            tags: url.tags().iter().map(|tag| tag.to_string()).collect(),
+            endpoint,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct EndpointDetailResponse {
+    #[serde(flatten)]
+    endpoint: EndpointResponse,
+    latency: LatencySummary,
+    checks: Vec<CheckResult>,
+    /// The latest check from every location, agents included
+    locations: Vec<LocationCheck>,
+}
+
+/// Latency of the returned checks in milliseconds
+#[derive(Serialize)]
+struct LatencySummary {
+    min: Option<i64>,
+    max: Option<i64>,
+    avg: Option<i64>,
+}
+
+impl LatencySummary {
+    fn new(checks: &[CheckResult]) -> Self {
+        let latencies = checks
+            .iter()
+            .filter_map(|check| check.latency)
+            .collect::<Vec<_>>();
+        let avg =
+            (!latencies.is_empty()).then(|| latencies.iter().sum::<i64>() / latencies.len() as i64);
+
+        Self {
+            min: latencies.iter().min().copied(),
+            max: latencies.iter().max().copied(),
+            avg,
+        }
+    }
+}
+
+/// Creates an endpoint, the options are the same as in `URLS`
+#[derive(Deserialize)]
+struct CreateEndpoint {
+    url: String,
+    #[serde(default)]
+    options: EndpointOptions,
+}
+
+#[derive(Deserialize)]
+struct UpdateEndpoint {
+    options: EndpointOptions,
+}
+
+/// The options as a JSON object or in the `name=api&tags=prod` form of
+/// `URLS`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EndpointOptions {
+    Map(BTreeMap<String, String>),
+    Query(String),
+}
+
+impl Default for EndpointOptions {
+    fn default() -> Self {
+        Self::Map(BTreeMap::new())
+    }
+}
+
+impl EndpointOptions {
+    fn into_map(self) -> Result<BTreeMap<String, String>, ApiError> {
+        let options = match self {
+            Self::Map(options) => return Ok(options),
+            Self::Query(options) => options,
+        };
+
+        options
+            .split('&')
+            .filter(|option| !option.is_empty())
+            .map(|option| {
+                option
+                    .split_once('=')
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .ok_or_else(|| {
+                        ApiError::BadRequest(format!(
+                            "Invalid option `{}`, expected `key=value`",
+                            option
+                        ))
+                    })
+            })
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct EndpointQuery {
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct IncidentQuery {
+    url: Option<String>,
+    since: Option<NaiveDateTime>,
+    until: Option<NaiveDateTime>,
+    maintenance: Option<bool>,
+    limit: Option<i64>,
+}
+
+async fn list_endpoints(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<EndpointResponse>>, ApiError> {
+    let urls = state.urls.borrow().clone();
+
+    let endpoints = state
+        .db
+        .endpoint
+        .get_all()
+        .await?
+        .into_iter()
+        .filter(|endpoint| urls.iter().any(|url| url.as_str() == endpoint.url.as_str()))
+        .map(EndpointResponse::from)
+        .collect();
+
+    Ok(Json(endpoints))
+}
+
+async fn get_endpoint(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<EndpointQuery>,
+) -> Result<Json<EndpointDetailResponse>, ApiError> {
+    let endpoint = find_endpoint(&state, &id).await?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_CHECKS_LIMIT)
+        .clamp(1, MAX_LIMIT);
+
+    let checks = state
+        .db
+        .check_result
+        .get_recent(&endpoint.url, limit)
+        .await?;
+    let locations = state.db.location_check.get_all(&endpoint.url).await?;
+
+    Ok(Json(EndpointDetailResponse {
+        endpoint: endpoint.into(),
+        latency: LatencySummary::new(&checks),
+        checks,
+        locations,
+    }))
+}
+
+async fn create_endpoint(
+    State(state): State<AppState>,
+    Json(body): Json<CreateEndpoint>,
+) -> Result<(StatusCode, Json<EndpointResponse>), ApiError> {
+    let url = build_url(&body.url, &body.options.into_map()?)?;
+    let urls = state.urls.borrow().clone();
+
+    if state
+        .db
+        .endpoint
+        .get_all()
+        .await?
+        .iter()
+        .any(|endpoint| endpoint.url.as_str() == url.as_str())
+    {
+        return Err(ApiError::Conflict(format!("{} already exists", url)));
+    }
+
+    let mut new_urls = urls.to_vec();
+    new_urls.push(url.clone());
+    validate_urls(&new_urls)?;
+
+    // A concurrent request for the same URL can pass the check above too
+    let endpoint = state.db.endpoint.create(&url).await.map_err(|e| {
+        if is_unique_violation(&e) {
+            ApiError::Conflict(format!("{} already exists", url))
+        } else {
+            ApiError::Internal(e)
+        }
+    })?;
+    state.urls.send_replace(Arc::new(new_urls));
+
+    Ok((StatusCode::CREATED, Json(endpoint.into())))
+}
+
+/// Replaces the options of an endpoint that was created through the API
+async fn update_endpoint(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(body): Json<UpdateEndpoint>,
+) -> Result<Json<EndpointResponse>, ApiError> {
+    let endpoint = find_api_endpoint(&state, &id).await?;
+    let url = build_url(&endpoint.url, &body.options.into_map()?)?;
+
+    let new_urls = state
+        .urls
+        .borrow()
+        .iter()
+        .map(|other| {
+            if other.as_str() == url.as_str() {
+                url.clone()
+            } else {
+                other.clone()
+            }
+        })
+        .collect::<Vec<_>>();
+    validate_urls(&new_urls)?;
+
+    let endpoint = state.db.endpoint.update_options(&url).await?;
+    state.urls.send_replace(Arc::new(new_urls));
+
+    Ok(Json(endpoint.into()))
+}
+
+async fn delete_endpoint(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let endpoint = find_api_endpoint(&state, &id).await?;
+
+    let new_urls = state
+        .urls
+        .borrow()
+        .iter()
+        .filter(|url| url.as_str() != endpoint.url.as_str())
+        .cloned()
+        .collect::<Vec<_>>();
+    validate_urls(&new_urls)?;
+
+    state.db.endpoint.delete(&endpoint.url).await?;
+    state.urls.send_replace(Arc::new(new_urls));
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_incidents(
+    State(state): State<AppState>,
+    Query(query): Query<IncidentQuery>,
+) -> Result<Json<Vec<Incident>>, ApiError> {
+    let filter = IncidentFilter {
+        url: query.url,
+        since: query.since,
+        until: query.until,
+        maintenance: query.maintenance,
+        limit: Some(query.limit.unwrap_or(MAX_LIMIT).clamp(1, MAX_LIMIT)),
+        ..Default::default()
+    };
+
+    let incidents = state.db.incident.find(&filter).await?;
+
+    Ok(Json(incidents))
+}
+
+/// The response that took the endpoint down, only failed HTTP checks have
+/// one
+async fn get_incident_snapshot(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Snapshot>, ApiError> {
+    let snapshot = state
+        .db
+        .snapshot
+        .get(&id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(Json(snapshot))
+}
+
+/// The URLs the agents check, with their options. Heartbeats are pushed to
+/// the monitor so agents skip them
+async fn list_agent_endpoints(State(state): State<AppState>) -> Json<Vec<String>> {
+    let urls = state
+        .urls
+        .borrow()
+        .iter()
+        .filter(|url| !url.is_heartbeat())
+        .map(|url| format!("{}#{}", url.as_str(), url.options_string()))
+        .collect();
+
+    Json(urls)
+}
+
+/// Stores the check of an agent, it's counted on the endpoint's next check
+async fn add_agent_check(
+    State(state): State<AppState>,
+    Json(body): Json<AgentCheck>,
+) -> Result<StatusCode, ApiError> {
+    if !state
+        .urls
+        .borrow()
+        .iter()
+        .any(|url| url.as_str() == body.url && !url.is_heartbeat())
+    {
+        return Err(ApiError::NotFound);
+    }
+
+    if body.location.trim().is_empty() {
+        return Err(ApiError::BadRequest("location can't be empty".to_string()));
+    }
+
+    // It would replace the monitor's own checks
+    if body.location == get_location() {
+        return Err(ApiError::Conflict(format!(
+            "{} is the monitor's location",
+            body.location
+        )));
+    }
+
+    // The agents' clocks can be off, the check counts from when it arrived
+    state
+        .db
+        .location_check
+        .set(&LocationCheck {
+            url: body.url,
+            location: body.location,
+            success: body.success,
+            latency: body.latency,
+            failure: body.failure,
+            failure_detail: body.detail,
+            checked_at: Local::now().naive_local(),
+        })
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn find_endpoint(state: &AppState, id: &str) -> Result<Endpoint, ApiError> {
+    state
+        .db
+        .endpoint
+        .get_by_id(id)
+        .await?
+        .ok_or(ApiError::NotFound)
+}
+
+/// Endpoints from `URLS` would be restored on the next start so only the
+/// ones created through the API can be changed
+async fn find_api_endpoint(state: &AppState, id: &str) -> Result<Endpoint, ApiError> {
+    let endpoint = find_endpoint(state, id).await?;
+
+    if endpoint.source != "api" {
+        return Err(ApiError::Conflict(format!(
+            "{} is configured through URLS",
+            endpoint.url
+        )));
+    }
+
+    Ok(endpoint)
+}
+
+fn build_url(url: &str, options: &BTreeMap<String, String>) -> Result<Url, ApiError> {
+    let is_valid = |value: &str| !value.contains(['#', '&', '=', ',']);
+
+    if !options
+        .iter()
+        .all(|(key, value)| is_valid(key) && is_valid(value))
+        || url.contains([',', '#'])
+    {
+        return Err(ApiError::BadRequest(
+            "The URL and options can't contain `#`, `&`, `=` or `,`".to_string(),
+        ));
+    }
+
+    let options = options
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    Ok(Url::from(format!("{}#{}", url, options)))
+}
+
+/// Makes sure the URLs would work as if they were set in `URLS`
+fn validate_urls(urls: &[Url]) -> Result<(), ApiError> {
+    let bad_request = |e: anyhow::Error| ApiError::BadRequest(e.to_string());
+
+    for url in urls.iter() {
+        let parsed = reqwest::Url::parse(url.as_str()).map_err(|e| bad_request(e.into()))?;
+        if !["http", "https", "tls", "dns", "heartbeat"].contains(&parsed.scheme()) {
+            return Err(ApiError::BadRequest(format!(
+                "Unsupported scheme `{}`",
+                parsed.scheme()
+            )));
+        }
+
+        CheckPolicy::from_url(url).map_err(bad_request)?;
+
+        if url.is_dns() {
+            DnsCheck::parse(url).map_err(bad_request)?;
+        }
+
+        if url.is_heartbeat() {
+            HeartbeatCheck::parse(url).map_err(bad_request)?;
+        }
+
+        if urls
+            .iter()
+            .filter(|other| other.name() == url.name())
+            .count()
+            > 1
+        {
+            return Err(ApiError::Conflict(format!(
+                "Another endpoint is named {}",
+                url.name()
+            )));
+        }
+    }
+
+    dependency::validate(urls).map_err(bad_request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Db;
+    use axum::body::Body;
+    use serde_json::{json, Value};
+    use teloxide::Bot;
+    use tokio::sync::watch;
+    use tower::ServiceExt;
+
+    /// Has characters that are encoded in a query string
+    const TOKEN: &str = "secret+/&=";
+
+    async fn app() -> (Router, watch::Receiver<Arc<Vec<Url>>>) {
+        let urls = vec![Url::from("https://example.com#name=example".to_string())];
+        let db = Db::in_memory(&urls).await.unwrap();
+        let (urls, urls_rx) = watch::channel(Arc::new(urls));
+
+        let state = AppState {
+            urls,
+            db: Arc::new(db),
+            bot: Arc::new(Bot::new("token")),
+            status_page: Default::default(),
+        };
+        let app = router(vec![TOKEN.to_string()]).with_state(state);
+
+        (app, urls_rx)
+    }
+
+    async fn send(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", TOKEN))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+            .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_requires_a_valid_token() {
+        let (app, _) = app().await;
+
+        for authorization in [None, Some("Bearer wrong"), Some("Bearer secret")] {
+            let mut request = Request::builder().method("POST").uri("/endpoints");
+            if let Some(authorization) = authorization {
+                request = request.header(header::AUTHORIZATION, authorization);
+            }
+
+            let response = app
+                .clone()
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_endpoint() {
+        let (app, urls) = app().await;
+
+        let body = json!({"url": "https://api.example.com", "options": {"name": "api"}});
+        let (status, endpoint) = send(&app, "POST", "/endpoints", Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(endpoint["name"], "api");
+
+        let body = json!({"url": "https://web.example.com", "options": "name=web&tags=prod"});
+        let (status, endpoint) = send(&app, "POST", "/endpoints", Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(endpoint["tags"], json!(["prod"]));
+
+        let names = urls
+            .borrow()
+            .iter()
+            .map(|url| url.name().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["example", "api", "web"]);
+    }
+
+    #[tokio::test]
+    async fn test_create_endpoint_bad_input() {
+        let (app, urls) = app().await;
+
+        let bad_requests = [
+            json!({"url": "ftp://files.example.com"}),
+            json!({"url": "https://api.example.com", "options": {"name": "a#b"}}),
+            json!({"url": "https://api.example.com", "options": "name"}),
+            json!({"url": "https://api.example.com", "options": {"interval": "soon"}}),
+            json!({"url": "https://api.example.com", "options": {"interval": "0"}}),
+            json!({"url": "https://api.example.com", "options": {"backoff": "-1"}}),
+            json!({"url": "https://api.example.com", "options": {"depends": "unknown"}}),
+        ];
+        for body in bad_requests {
+            let (status, _) = send(&app, "POST", "/endpoints", Some(body.clone())).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        }
+
+        let body = json!({"url": "https://example.com"});
+        let (status, _) = send(&app, "POST", "/endpoints", Some(body)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let body = json!({"url": "https://other.example.com", "options": {"name": "example"}});
+        let (status, _) = send(&app, "POST", "/endpoints", Some(body)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        assert_eq!(urls.borrow().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unique_violation() {
+        let url = Url::from("https://example.com".to_string());
+        let db = Db::in_memory(std::slice::from_ref(&url)).await.unwrap();
+
+        let e = db.endpoint.create(&url).await.unwrap_err();
+        assert!(is_unique_violation(&e));
+        assert!(!is_unique_violation(&anyhow::anyhow!("Not a db error")));
+    }
+
+    #[tokio::test]
+    async fn test_update_and_delete_endpoint() {
+        let (app, urls) = app().await;
+
+        let body = json!({"url": "https://api.example.com", "options": {"name": "api"}});
+        let (_, endpoint) = send(&app, "POST", "/endpoints", Some(body)).await;
+        let uri = format!("/endpoints/{}", endpoint["id"].as_str().unwrap());
+
+        let body = json!({"options": {"name": "api", "tags": "prod"}});
+        let (status, endpoint) = send(&app, "PUT", &uri, Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(endpoint["tags"], json!(["prod"]));
+        assert_eq!(urls.borrow()[1].tags(), vec!["prod"]);
+
+        let body = json!({"options": {"interval": "soon"}});
+        let (status, _) = send(&app, "PUT", &uri, Some(body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = send(&app, "DELETE", &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(urls.borrow().len(), 1);
+
+        let (status, _) = send(&app, "DELETE", &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_endpoints_from_urls_are_read_only() {
+        let (app, _) = app().await;
+
+        let (_, endpoints) = send(&app, "GET", "/endpoints", None).await;
+        let uri = format!("/endpoints/{}", endpoints[0]["id"].as_str().unwrap());
+
+        let body = json!({"options": {"name": "renamed"}});
+        let (status, _) = send(&app, "PUT", &uri, Some(body)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, _) = send(&app, "DELETE", &uri, None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_query_token_only_for_events() {
+        let (app, _) = app().await;
+
+        let get = |uri: &str| {
+            let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            app.clone().oneshot(request)
+        };
+
+        let response = get("/events?token=secret%2B%2F%26%3D").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = get("/events?token=secret+/&=").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = get("/endpoints?token=secret%2B%2F%26%3D").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_agent_endpoints() {
+        let (app, urls) = app().await;
+
+        let body = json!({"url": "heartbeat://backup?period=86400", "options": {"name": "backup"}});
+        let (status, _) = send(&app, "POST", "/endpoints", Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(urls.borrow().len(), 2);
+
+        // Heartbeats are pushed to the monitor
+        let (status, endpoints) = send(&app, "GET", "/agent/endpoints", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            endpoints,
+            json!([format!(
+                "https://example.com#{}",
+                urls.borrow()[0].options_string()
+            )])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_agent_check() {
+        let (app, _) = app().await;
+
+        let check = |location: &str, url: &str| {
+            json!({
+                "location": location,
+                "url": url,
+                "success": false,
+                "latency": 120,
+                "failure": "timeout",
+                "detail": "No response after 10s",
+            })
+        };
+
+        let body = check("eu", "https://unknown.example.com");
+        let (status, _) = send(&app, "POST", "/agent/checks", Some(body)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let body = json!({"url": "heartbeat://backup?period=86400", "options": {"name": "backup"}});
+        send(&app, "POST", "/endpoints", Some(body)).await;
+        let body = check("eu", "heartbeat://backup?period=86400");
+        let (status, _) = send(&app, "POST", "/agent/checks", Some(body)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let body = check(" ", "https://example.com");
+        let (status, _) = send(&app, "POST", "/agent/checks", Some(body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let body = check(&get_location(), "https://example.com");
+        let (status, _) = send(&app, "POST", "/agent/checks", Some(body)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let body = check("eu", "https://example.com");
+        let (status, _) = send(&app, "POST", "/agent/checks", Some(body)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (_, endpoints) = send(&app, "GET", "/endpoints", None).await;
+        let uri = format!("/endpoints/{}", endpoints[0]["id"].as_str().unwrap());
+        let (_, endpoint) = send(&app, "GET", &uri, None).await;
+        assert_eq!(endpoint["locations"][0]["location"], "eu");
+        assert_eq!(endpoint["locations"][0]["failure"], "timeout");
+    }
+}
@@ -0,0 +1,149 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{Duration, Local};
+use tracing::error;
+
+use super::AppState;
+use crate::{db::endpoint::Status, metrics::METRICS};
+
+const CACHE_CONTROL: &str = "public, max-age=60";
+const UPTIME_DAYS: i64 = 30;
+
+const GREEN: &str = "#4c1";
+const YELLOW: &str = "#dfb317";
+const ORANGE: &str = "#fe7d37";
+const RED: &str = "#e05d44";
+const GREY: &str = "#9f9f9f";
+
+/// `status.svg`, `uptime-30d.svg` or `latency.svg` of a public endpoint
+pub async fn badge(
+    State(state): State<AppState>,
+    Path((name, badge)): Path<(String, String)>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let urls = state.urls.borrow().clone();
+    let url = urls
+        .iter()
+        .find(|url| url.is_public() && url.name() == name)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let internal_error = |e: anyhow::Error| {
+        METRICS.record_error(&e);
+        error!(error = %e, "Couldn't render the badge");
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    let (label, message, color) = match badge.as_str() {
+        "status.svg" => {
+            let endpoint = state.db.endpoint.get(url).await.map_err(internal_error)?;
+            match endpoint.status {
+                Status::Up => ("status", "up".to_string(), GREEN),
+                Status::Down => ("status", "down".to_string(), RED),
+                Status::Pending => ("status", "pending".to_string(), GREY),
+            }
+        }
+        "uptime-30d.svg" => {
+            let since = Local::now().naive_local() - Duration::days(UPTIME_DAYS);
+            let days = state
+                .db
+                .check_result
+                .daily_uptime(url.as_str(), since)
+                .await
+                .map_err(internal_error)?;
+
+            let checks = days.iter().map(|day| day.checks).sum::<i64>();
+            let successes = days.iter().map(|day| day.successes).sum::<i64>();
+
+            match checks {
+                0 => ("uptime 30d", "n/a".to_string(), GREY),
+                _ => {
+                    let uptime = successes as f64 * 100.0 / checks as f64;
+                    ("uptime 30d", format_uptime(uptime), uptime_color(uptime))
+                }
+            }
+        }
+        "latency.svg" => {
+            let check = state
+                .db
+                .check_result
+                .get_recent(url.as_str(), 1)
+                .await
+                .map_err(internal_error)?;
+
+            match check.first().and_then(|check| check.latency) {
+                Some(latency) => ("latency", format!("{}ms", latency), latency_color(latency)),
+                None => ("latency", "n/a".to_string(), GREY),
+            }
+        }
+        _ => return Err(StatusCode::NOT_FOUND),
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            (header::CACHE_CONTROL, CACHE_CONTROL),
+        ],
+        render(label, &message, color),
+    ))
+}
+
+fn format_uptime(uptime: f64) -> String {
+    if uptime >= 100.0 {
+        "100%".to_string()
+    } else {
+        format!("{:.2}%", uptime)
+    }
+}
+
+fn uptime_color(uptime: f64) -> &'static str {
+    match uptime {
+        uptime if uptime >= 99.9 => GREEN,
+        uptime if uptime >= 99.0 => YELLOW,
+        uptime if uptime >= 95.0 => ORANGE,
+        _ => RED,
+    }
+}
+
+fn latency_color(latency: i64) -> &'static str {
+    match latency {
+        0..=300 => GREEN,
+        301..=1000 => YELLOW,
+        _ => ORANGE,
+    }
+}
+
+/// Approximate width of the text in Verdana 11px, close enough for the
+/// short labels of the badges
+fn text_width(text: &str) -> usize {
+    text.chars().count() * 7 + 10
+}
+
+/// A flat shields.io style badge
+fn render(label: &str, message: &str, color: &str) -> String {
+    let label_width = text_width(label);
+    let message_width = text_width(message);
+    let width = label_width + message_width;
+    let label_x = label_width * 5;
+    let message_x = (label_width + message_width / 2) * 10;
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}"><title>{label}: {message}</title><linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient><clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath><g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="#555"/><rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/><rect width="{width}" height="20" fill="url(#s)"/></g><g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="110"><text x="{label_x}" y="150" fill="#010101" fill-opacity=".3" transform="scale(.1)">{label}</text><text x="{label_x}" y="140" transform="scale(.1)">{label}</text><text x="{message_x}" y="150" fill="#010101" fill-opacity=".3" transform="scale(.1)">{message}</text><text x="{message_x}" y="140" transform="scale(.1)">{message}</text></g></svg>"##
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let svg = render("status", "up", GREEN);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(r#"width="76""#));
+        assert!(svg.contains(r##"<rect x="52" width="24" height="20" fill="#4c1"/>"##));
+        assert!(svg.contains("<title>status: up</title>"));
+    }
+}
@@ -0,0 +1,70 @@
+use axum::{
+    extract::State,
+    response::sse::{Event as SseEvent, KeepAlive, Sse},
+};
+use serde::Serialize;
+use std::{convert::Infallible, time::Duration};
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    Stream, StreamExt,
+};
+
+use super::{api::ApiError, AppState};
+use crate::{
+    db::endpoint::Status,
+    events::{Event, EVENTS},
+};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Serialize)]
+struct EndpointState {
+    name: String,
+    url: String,
+    status: Status,
+}
+
+/// Streams the checks and status changes, starting with a `snapshot` of
+/// every endpoint
+pub async fn events(
+    State(state): State<AppState>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>, ApiError> {
+    // Subscribe first so nothing is missed between the snapshot and the stream
+    let receiver = EVENTS.subscribe();
+
+    let urls = state.urls.borrow().clone();
+    let endpoints = state.db.endpoint.get_all().await?;
+    let snapshot = urls
+        .iter()
+        .filter_map(|url| {
+            let endpoint = endpoints.iter().find(|e| e.url.as_str() == url.as_str())?;
+            Some(EndpointState {
+                name: url.name().to_string(),
+                url: url.as_str().to_string(),
+                status: endpoint.status,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let snapshot = SseEvent::default()
+        .event("snapshot")
+        .json_data(&snapshot)
+        .map_err(|e| ApiError::Internal(e.into()))?;
+
+    let updates = BroadcastStream::new(receiver).filter_map(|event| match event {
+        Ok(event) => to_sse(&event),
+        // A slow client misses the events it couldn't keep up with
+        Err(BroadcastStreamRecvError::Lagged(_)) => None,
+    });
+
+    let stream = tokio_stream::once(snapshot).chain(updates).map(Ok);
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::new().interval(HEARTBEAT_INTERVAL)))
+}
+
+fn to_sse(event: &Event) -> Option<SseEvent> {
+    SseEvent::default()
+        .event(event.name())
+        .json_data(event)
+        .ok()
+}
@@ -0,0 +1,229 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{Local, NaiveDateTime};
+use std::fmt::Write;
+use tracing::error;
+
+use super::{escape, AppState};
+use crate::{
+    constants::get_status_page_title,
+    db::{
+        incident::{Incident, IncidentFilter},
+        url::Url,
+    },
+    metrics::METRICS,
+};
+
+const FEED_LIMIT: i64 = 50;
+
+/// Incidents of all the public endpoints
+pub async fn feed(State(state): State<AppState>) -> Result<impl IntoResponse, StatusCode> {
+    let urls = state.urls.borrow().clone();
+    let public = urls
+        .iter()
+        .filter(|url| url.is_public())
+        .collect::<Vec<_>>();
+    if public.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let filter = IncidentFilter {
+        urls: Some(public.iter().map(|url| url.as_str().to_string()).collect()),
+        limit: Some(FEED_LIMIT),
+        ..Default::default()
+    };
+    let incidents = find_incidents(&state, &filter).await?;
+    let incidents = incidents
+        .into_iter()
+        .filter_map(|incident| {
+            let url = public.iter().find(|url| url.as_str() == incident.url)?;
+            Some((*url, incident))
+        })
+        .collect::<Vec<_>>();
+
+    let title = get_status_page_title();
+    Ok(atom_response(render(&title, "all", &incidents)))
+}
+
+/// Incidents of a single public endpoint
+pub async fn endpoint_feed(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let urls = state.urls.borrow().clone();
+    let url = urls
+        .iter()
+        .find(|url| url.is_public() && url.name() == name)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let filter = IncidentFilter {
+        url: Some(url.as_str().to_string()),
+        limit: Some(FEED_LIMIT),
+        ..Default::default()
+    };
+    let incidents = find_incidents(&state, &filter)
+        .await?
+        .into_iter()
+        .map(|incident| (url, incident))
+        .collect::<Vec<_>>();
+
+    let title = format!("{} - {}", get_status_page_title(), url.name());
+    Ok(atom_response(render(&title, url.name(), &incidents)))
+}
+
+async fn find_incidents(
+    state: &AppState,
+    filter: &IncidentFilter,
+) -> Result<Vec<Incident>, StatusCode> {
+    state.db.incident.find(filter).await.map_err(|e| {
+        METRICS.record_error(&e);
+        error!(error = %e, "Couldn't render the feed");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+fn atom_response(body: String) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+        body,
+    )
+}
+
+/// One entry when the incident started and another one once it's resolved,
+/// newest first
+fn render(title: &str, feed_id: &str, incidents: &[(&Url, Incident)]) -> String {
+    let mut entries = Vec::new();
+
+    for (url, incident) in incidents.iter() {
+        let kind = if incident.maintenance {
+            " during maintenance"
+        } else {
+            ""
+        };
+
+        if let Some(resolved_at) = incident.resolved_at {
+            let duration = format_duration(resolved_at - incident.created_at);
+            entries.push((
+                resolved_at,
+                format!("{}:resolved", incident.id),
+                format!("{} is back up", url.name()),
+                format!(
+                    "{} was down for {}{}. Cause: {}",
+                    url.name(),
+                    duration,
+                    kind,
+                    incident.message
+                ),
+            ));
+        }
+
+        entries.push((
+            incident.created_at,
+            format!("{}:started", incident.id),
+            format!("{} is down", url.name()),
+            format!(
+                "{} went down{}. Cause: {}",
+                url.name(),
+                kind,
+                incident.message
+            ),
+        ));
+    }
+
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.0));
+
+    let updated = entries
+        .first()
+        .map(|entry| entry.0)
+        .unwrap_or_else(|| Local::now().naive_local());
+
+    let mut xml = String::new();
+    let _ = write!(
+        xml,
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+<title>{}</title>
+<id>urn:server-monitor:feed:{}</id>
+<updated>{}</updated>
+"#,
+        escape(title),
+        escape(feed_id),
+        rfc3339(updated)
+    );
+
+    for (time, id, title, summary) in entries.iter() {
+        let _ = write!(
+            xml,
+            r#"<entry>
+<title>{}</title>
+<id>urn:server-monitor:incident:{}</id>
+<updated>{}</updated>
+<author><name>Server Monitor</name></author>
+<summary>{}</summary>
+</entry>
+"#,
+            escape(title),
+            escape(id),
+            rfc3339(*time),
+            escape(summary)
+        );
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+/// The incident times are stored in local time
+fn rfc3339(time: NaiveDateTime) -> String {
+    time.and_local_timezone(Local)
+        .earliest()
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_else(|| time.and_utc().to_rfc3339())
+}
+
+fn format_duration(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes();
+
+    match minutes {
+        0 => format!("{}s", duration.num_seconds()),
+        1..=59 => format!("{}m", minutes),
+        _ => format!("{}h {}m", minutes / 60, minutes % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_render() {
+        let url = Url::from("https://example.com#name=api".to_string());
+        let created_at = NaiveDate::from_ymd_opt(2024, 6, 1)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        let incident = Incident {
+            id: "abc".to_string(),
+            url: url.as_str().to_string(),
+            message: "example.com was down!".to_string(),
+            created_at,
+            maintenance: false,
+            reported: true,
+            resolved_at: Some(created_at + chrono::Duration::minutes(90)),
+            failure: None,
+            failure_detail: None,
+        };
+
+        let xml = render("Status & co", "all", &[(&url, incident)]);
+
+        assert!(xml.contains("<title>Status &amp; co</title>"));
+        assert!(xml.contains("<id>urn:server-monitor:incident:abc:started</id>"));
+        assert!(xml.contains("api was down for 1h 30m. Cause: example.com was down!"));
+        // The resolution is the newest entry
+        assert!(xml.find("api is back up").unwrap() < xml.find("api is down").unwrap());
+    }
+}
@@ -0,0 +1,15 @@
+use axum::{http::StatusCode, Json};
+
+use crate::health::{HealthReport, HEALTH};
+
+/// `503` when the checks are late or their results aren't written anymore
+pub async fn healthz() -> (StatusCode, Json<HealthReport>) {
+    let report = HEALTH.report();
+    let status = if report.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(report))
+}
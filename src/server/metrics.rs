@@ -0,0 +1,22 @@
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use prometheus::TEXT_FORMAT;
+use tracing::error;
+
+use super::AppState;
+use crate::metrics::METRICS;
+
+pub async fn metrics(State(state): State<AppState>) -> Result<impl IntoResponse, StatusCode> {
+    let urls = state.urls.borrow().clone();
+
+    let body = METRICS.render(&urls, &state.db).await.map_err(|e| {
+        METRICS.record_error(&e);
+        error!(error = %e, "Couldn't render the metrics");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(([(header::CONTENT_TYPE, TEXT_FORMAT)], body))
+}
@@ -0,0 +1,87 @@
+mod api;
+mod badge;
+mod events;
+mod feed;
+mod health;
+mod metrics;
+mod ping;
+mod status_page;
+
+use axum::{
+    routing::{any, get},
+    Router,
+};
+use std::sync::Arc;
+use teloxide::Bot;
+use tokio::sync::watch;
+use tracing::error;
+
+use crate::{
+    constants::get_api_tokens,
+    db::{url::Url, Db},
+};
+use status_page::{create_status_page_cron, StatusPage};
+
+const DEFAULT_PORT: u16 = 3000;
+
+#[derive(Clone)]
+pub struct AppState {
+    /// The monitored URLs, changing them restarts the scheduler
+    pub urls: watch::Sender<Arc<Vec<Url>>>,
+    pub db: Arc<Db>,
+    pub bot: Arc<Bot>,
+    pub status_page: StatusPage,
+}
+
+/// Starts the HTTP server in the background on `PORT`
+pub async fn create_server(
+    urls: watch::Sender<Arc<Vec<Url>>>,
+    db: Arc<Db>,
+    bot: Arc<Bot>,
+) -> anyhow::Result<()> {
+    let port = std::env::var("PORT")
+        .unwrap_or_else(|_| DEFAULT_PORT.to_string())
+        .parse::<u16>()?;
+
+    let status_page = StatusPage::default();
+    create_status_page_cron(urls.subscribe(), Arc::clone(&db), Arc::clone(&status_page));
+
+    let state = AppState {
+        urls,
+        db,
+        bot,
+        status_page,
+    };
+
+    let app = Router::new()
+        .route("/healthz", get(health::healthz))
+        .route("/status", get(status_page::status_page))
+        .route("/metrics", get(metrics::metrics))
+        .route("/badge/{name}/{badge}", get(badge::badge))
+        .route("/feed", get(feed::feed))
+        .route("/feed/{name}", get(feed::endpoint_feed))
+        .route("/ping/{name}", any(ping::ping))
+        .route("/ping/{name}/fail", any(ping::ping_fail))
+        .nest("/api", api::router(get_api_tokens()))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port)).await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            error!(error = %e, "HTTP server stopped");
+        }
+    });
+
+    Ok(())
+}
+
+/// Escapes text for HTML and XML
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
@@ -0,0 +1,79 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+};
+use tracing::error;
+
+use super::AppState;
+use crate::{heartbeat::HeartbeatCheck, metrics::METRICS, status::check_url_status, tasks::track};
+
+/// Log excerpts sent with a failed ping are cut to this many characters
+const MAX_PING_MESSAGE_LENGTH: usize = 1000;
+
+pub async fn ping(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<&'static str, StatusCode> {
+    record_ping(&state, &name, true, None).await
+}
+
+/// The request body is stored as the log excerpt of the failure
+pub async fn ping_fail(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    body: String,
+) -> Result<&'static str, StatusCode> {
+    let message = body
+        .trim()
+        .chars()
+        .take(MAX_PING_MESSAGE_LENGTH)
+        .collect::<String>();
+    let message = (!message.is_empty()).then_some(message);
+
+    record_ping(&state, &name, false, message.as_deref()).await
+}
+
+/// The ping is refused once the monitor is shutting down
+async fn record_ping(
+    state: &AppState,
+    name: &str,
+    success: bool,
+    message: Option<&str>,
+) -> Result<&'static str, StatusCode> {
+    track(apply_ping(state, name, success, message))
+        .await
+        .unwrap_or(Err(StatusCode::SERVICE_UNAVAILABLE))
+}
+
+async fn apply_ping(
+    state: &AppState,
+    name: &str,
+    success: bool,
+    message: Option<&str>,
+) -> Result<&'static str, StatusCode> {
+    let urls = state.urls.borrow().clone();
+    let url = urls
+        .iter()
+        .filter(|url| url.is_heartbeat())
+        .find(|url| HeartbeatCheck::parse(url).is_ok_and(|check| check.name == name))
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    state
+        .db
+        .heartbeat
+        .ping(url, success, message)
+        .await
+        .map_err(|e| {
+            METRICS.record_error(&e);
+            error!(name, error = %e, "Couldn't record the ping");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    // Apply the ping right away instead of waiting for the next check
+    if let Err(e) = check_url_status(url, &urls, &state.bot, &state.db).await {
+        METRICS.record_error(&e);
+        error!(name, error = %e, "Check failed");
+    }
+
+    Ok("OK")
+}
@@ -0,0 +1,367 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime};
+use std::{collections::BTreeMap, fmt::Write, sync::Arc, time};
+use tokio::sync::{watch, RwLock};
+use tracing::error;
+
+use super::{escape, AppState};
+use crate::{
+    constants::{get_status_page_color, get_status_page_logo, get_status_page_title},
+    db::{
+        endpoint::Status,
+        incident::{Incident, IncidentFilter},
+        url::Url,
+        Db,
+    },
+    maintenance::is_in_maintenance,
+    metrics::METRICS,
+    STATUS_PAGE_DAYS, STATUS_PAGE_REFRESH_INTERVAL,
+};
+
+/// Endpoints without a `group` option are listed under this one
+const DEFAULT_GROUP: &str = "Services";
+const RECENT_INCIDENTS_DAYS: i64 = 7;
+const RECENT_INCIDENTS_LIMIT: i64 = 20;
+
+/// The rendered page, `None` while no endpoint is public
+pub type StatusPage = Arc<RwLock<Option<String>>>;
+
+pub async fn status_page(State(state): State<AppState>) -> Response {
+    match state.status_page.read().await.as_ref() {
+        Some(html) => Html(html.clone()).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Renders the page again whenever an endpoint goes up or down, the
+/// endpoints change, or at least every `STATUS_PAGE_REFRESH_INTERVAL`
+pub fn create_status_page_cron(
+    mut urls: watch::Receiver<Arc<Vec<Url>>>,
+    db: Arc<Db>,
+    page: StatusPage,
+) {
+    let mut status_changes = db.status_changes.subscribe();
+    let interval = time::Duration::from_millis(STATUS_PAGE_REFRESH_INTERVAL);
+
+    tokio::spawn(async move {
+        loop {
+            let current = urls.borrow_and_update().clone();
+
+            match generate(&current, &db).await {
+                Ok(html) => *page.write().await = html,
+                Err(e) => {
+                    METRICS.record_error(&e);
+                    error!(error = %e, "Couldn't render the status page");
+                }
+            }
+
+            tokio::select! {
+                _ = status_changes.changed() => {}
+                _ = urls.changed() => {}
+                _ = tokio::time::sleep(interval) => {}
+            }
+        }
+    });
+}
+
+/// A public endpoint as it's shown on the page
+struct PublicEndpoint<'a> {
+    name: &'a str,
+    status: Status,
+    unreachable: bool,
+    maintenance: bool,
+    /// The uptime percentage of each day, oldest first, `None` without checks
+    days: Vec<Option<f64>>,
+}
+
+struct Branding {
+    title: String,
+    logo: Option<String>,
+    color: String,
+}
+
+async fn generate(urls: &[Url], db: &Db) -> anyhow::Result<Option<String>> {
+    let public = urls
+        .iter()
+        .filter(|url| url.is_public())
+        .collect::<Vec<_>>();
+    if public.is_empty() {
+        return Ok(None);
+    }
+
+    let now = Local::now().naive_local();
+    let today = now.date();
+    let first_day = today - Duration::days(STATUS_PAGE_DAYS - 1);
+    let endpoints = db.endpoint.get_all().await?;
+
+    let mut groups: BTreeMap<&str, Vec<PublicEndpoint>> = BTreeMap::new();
+    for url in public.iter() {
+        let Some(endpoint) = endpoints.iter().find(|e| e.url.as_str() == url.as_str()) else {
+            continue;
+        };
+
+        let since = first_day.and_hms_opt(0, 0, 0).unwrap_or(now);
+        let uptime = db.check_result.daily_uptime(url.as_str(), since).await?;
+        let days = (0..STATUS_PAGE_DAYS)
+            .map(|i| first_day + Duration::days(i))
+            .map(|day| {
+                uptime
+                    .iter()
+                    .find(|uptime| uptime.day == day)
+                    .map(|uptime| uptime.percentage())
+            })
+            .collect();
+
+        groups
+            .entry(url.group().unwrap_or(DEFAULT_GROUP))
+            .or_default()
+            .push(PublicEndpoint {
+                name: url.name(),
+                status: endpoint.status,
+                unreachable: endpoint.unreachable_via.is_some(),
+                maintenance: is_in_maintenance(url, now),
+                days,
+            });
+    }
+
+    let filter = IncidentFilter {
+        urls: Some(public.iter().map(|url| url.as_str().to_string()).collect()),
+        since: Some(now - Duration::days(RECENT_INCIDENTS_DAYS)),
+        limit: Some(RECENT_INCIDENTS_LIMIT),
+        ..Default::default()
+    };
+    let incidents = db
+        .incident
+        .find(&filter)
+        .await?
+        .into_iter()
+        .filter_map(|incident| {
+            let url = public.iter().find(|url| url.as_str() == incident.url)?;
+            Some((url.name(), incident))
+        })
+        .collect::<Vec<_>>();
+
+    let branding = Branding {
+        title: get_status_page_title(),
+        logo: get_status_page_logo(),
+        color: get_status_page_color(),
+    };
+
+    Ok(Some(render(&branding, &groups, &incidents, first_day, now)))
+}
+
+fn render(
+    branding: &Branding,
+    groups: &BTreeMap<&str, Vec<PublicEndpoint>>,
+    incidents: &[(&str, Incident)],
+    first_day: NaiveDate,
+    now: NaiveDateTime,
+) -> String {
+    let endpoints = groups.values().flatten().collect::<Vec<_>>();
+    let down = endpoints
+        .iter()
+        .filter(|endpoint| endpoint.status == Status::Down)
+        .collect::<Vec<_>>();
+
+    let mut html = String::new();
+    let title = escape(&branding.title);
+
+    let _ = write!(
+        html,
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; max-width: 860px; margin: 0 auto; padding: 24px; color: #1f2937; }}
+header {{ display: flex; align-items: center; gap: 12px; border-bottom: 3px solid {color}; padding-bottom: 12px; }}
+header img {{ height: 40px; }}
+.summary {{ padding: 16px; border-radius: 6px; color: #fff; font-weight: 600; margin: 24px 0; }}
+.ok {{ background: #16a34a; }} .down {{ background: #dc2626; }}
+.endpoint {{ border: 1px solid #e5e7eb; border-radius: 6px; padding: 12px; margin-bottom: 12px; }}
+.endpoint .head {{ display: flex; justify-content: space-between; }}
+.bars {{ display: flex; gap: 2px; margin-top: 8px; }}
+.bars span {{ flex: 1; height: 28px; border-radius: 2px; background: #e5e7eb; }}
+.bars .full {{ background: #16a34a; }} .bars .high {{ background: #84cc16; }}
+.bars .low {{ background: #f59e0b; }} .bars .bad {{ background: #dc2626; }}
+.range {{ display: flex; justify-content: space-between; font-size: 12px; color: #6b7280; }}
+.status-UP {{ color: #16a34a; }} .status-DOWN {{ color: #dc2626; }} .status-PENDING {{ color: #6b7280; }}
+footer {{ font-size: 12px; color: #6b7280; margin-top: 32px; }}
+</style>
+</head>
+<body>
+<header>"#,
+        color = escape(&branding.color),
+    );
+
+    if let Some(logo) = &branding.logo {
+        let _ = write!(html, r#"<img src="{}" alt="">"#, escape(logo));
+    }
+
+    let _ = writeln!(html, "<h1>{}</h1></header>", title);
+
+    if down.is_empty() {
+        html.push_str(r#"<div class="summary ok">All systems operational</div>"#);
+    } else {
+        html.push_str(r#"<div class="summary down">Some systems are down</div>"#);
+        html.push_str("<h2>Active incidents</h2><ul>");
+
+        for endpoint in down.iter() {
+            let problem = if endpoint.unreachable {
+                "is unreachable"
+            } else {
+                "is down"
+            };
+            let _ = write!(html, "<li>{} {}</li>", escape(endpoint.name), problem);
+        }
+
+        html.push_str("</ul>");
+    }
+
+    for (group, endpoints) in groups.iter() {
+        let _ = write!(html, "<h2>{}</h2>", escape(group));
+
+        for endpoint in endpoints.iter() {
+            let (class, label) = match endpoint.status {
+                _ if endpoint.maintenance => ("PENDING", "Maintenance"),
+                Status::Up => ("UP", "Operational"),
+                Status::Down if endpoint.unreachable => ("DOWN", "Unreachable"),
+                Status::Down => ("DOWN", "Down"),
+                Status::Pending => ("PENDING", "Pending"),
+            };
+
+            let _ = write!(
+                html,
+                r#"<div class="endpoint"><div class="head"><strong>{}</strong><span class="status-{}">{}</span></div><div class="bars">"#,
+                escape(endpoint.name),
+                class,
+                label
+            );
+
+            for (i, uptime) in endpoint.days.iter().enumerate() {
+                let day = first_day + Duration::days(i as i64);
+                let (class, tooltip) = match uptime {
+                    Some(uptime) => (bar_class(*uptime), format!("{:.2}%", uptime)),
+                    None => ("", "No data".to_string()),
+                };
+                let _ = write!(
+                    html,
+                    r#"<span class="{}" title="{}: {}"></span>"#,
+                    class,
+                    day.format("%d/%m/%Y"),
+                    tooltip
+                );
+            }
+
+            let _ = write!(
+                html,
+                r#"</div><div class="range"><span>{} days ago</span><span>{}</span><span>Today</span></div></div>"#,
+                endpoint.days.len(),
+                overall_uptime(&endpoint.days)
+                    .map(|uptime| format!("{:.2}% uptime", uptime))
+                    .unwrap_or_default()
+            );
+        }
+    }
+
+    html.push_str("<h2>Recent incidents</h2>");
+
+    if incidents.is_empty() {
+        let _ = write!(
+            html,
+            "<p>No incidents in the last {} days.</p>",
+            RECENT_INCIDENTS_DAYS
+        );
+    } else {
+        html.push_str("<ul>");
+
+        for (name, incident) in incidents.iter() {
+            let kind = if incident.maintenance {
+                "Scheduled maintenance"
+            } else {
+                "Outage"
+            };
+            let _ = write!(
+                html,
+                "<li><strong>{}</strong> {} - {}</li>",
+                escape(name),
+                kind,
+                incident.created_at.format("%d/%m/%Y %I:%M %p")
+            );
+        }
+
+        html.push_str("</ul>");
+    }
+
+    let _ = write!(
+        html,
+        "<footer>Last updated {}</footer>\n</body>\n</html>\n",
+        now.format("%d/%m/%Y %I:%M %p")
+    );
+
+    html
+}
+
+fn bar_class(uptime: f64) -> &'static str {
+    match uptime {
+        uptime if uptime >= 100.0 => "full",
+        uptime if uptime >= 99.0 => "high",
+        uptime if uptime >= 95.0 => "low",
+        _ => "bad",
+    }
+}
+
+/// The average uptime of the days with checks
+fn overall_uptime(days: &[Option<f64>]) -> Option<f64> {
+    let days = days.iter().flatten().collect::<Vec<_>>();
+    if days.is_empty() {
+        return None;
+    }
+
+    Some(days.iter().copied().sum::<f64>() / days.len() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let branding = Branding {
+            title: "Acme <Status>".to_string(),
+            logo: None,
+            color: "#000".to_string(),
+        };
+        let mut days = vec![None; 88];
+        days.extend([Some(100.0), Some(50.0)]);
+
+        let mut groups = BTreeMap::new();
+        groups.insert(
+            "Core",
+            vec![PublicEndpoint {
+                name: "api",
+                status: Status::Down,
+                unreachable: false,
+                maintenance: false,
+                days,
+            }],
+        );
+
+        let first_day = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let now = first_day.and_hms_opt(12, 0, 0).unwrap();
+        let html = render(&branding, &groups, &[], first_day, now);
+
+        assert!(html.contains("<h1>Acme &lt;Status&gt;</h1>"));
+        assert!(html.contains("<li>api is down</li>"));
+        assert!(html.contains("<h2>Core</h2>"));
+        assert!(html.contains("75.00% uptime"));
+        assert_eq!(html.matches("<span class=\"\" title=").count(), 88);
+        assert!(html.contains(r#"<span class="bad" title="30/03/2024: 50.00%">"#));
+    }
+}
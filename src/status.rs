@@ -1,11 +1,34 @@
 use crate::{
     bot::{notify, NotifyOpts},
-    db::{endpoint::Status, url::Url, Db},
-    UPDATE_INTERVAL,
+    constants::{
+        get_cert_check_interval, get_cert_warn_days, get_down_threshold, get_flap_threshold,
+        get_flap_window, get_leader_lease, get_up_threshold,
+    },
+    db::{
+        endpoint::{Endpoint, Lookup, Status},
+        url::Url,
+        Db,
+    },
+    dependency::{dependents, parents},
+    events::publish_check,
+    leader::LEADER,
+    maintenance::{get_maintenance_windows, is_in_maintenance},
+    metrics::METRICS,
+    quorum,
+    tasks::track,
+    telemetry,
+    tls::{warn_threshold, EXPIRED},
+    CHECK_RETENTION_DAYS, INCIDENT_RETENTION_DAYS, MAINTENANCE_CHECK_INTERVAL, UPDATE_INTERVAL,
 };
-use chrono::Local;
-use std::{sync::Arc, time::Duration};
+use chrono::{Local, NaiveDateTime};
+use std::{collections::HashSet, sync::Arc, time::Duration};
 use teloxide::Bot;
+use tokio::sync::watch;
+use tracing::{error, info, warn, Span};
+
+/// Characters of the failing response body quoted in the down alert, the
+/// rest of the snapshot is available through the API
+const ALERT_BODY_EXCERPT_LENGTH: usize = 200;
 
 /// Gets the incidents from the db and creates a Telegram message and returns the String
 async fn server_update_message(db: &Db) -> anyhow::Result<String> {
@@ -13,8 +36,8 @@ async fn server_update_message(db: &Db) -> anyhow::Result<String> {
 
     let endpoints = db.endpoint.get_all().await?;
 
-    let all_up =
-        db.incident.is_empty().await? && endpoints.iter().all(|value| value.status == Status::Up);
+    let all_up = db.incident.get_unreported().await?.is_empty()
+        && endpoints.iter().all(|value| value.status == Status::Up);
 
     if all_up {
         message.push_str("✅ No new incidents have happened so far.\n\n");
@@ -43,18 +66,23 @@ async fn server_update_message(db: &Db) -> anyhow::Result<String> {
             message.push_str(&format!("Uptime: {:?} days and {:?} hours\n", days, hours));
         }
 
+        if let Some(flapping_since) = endpoint.flapping_since {
+            let since = flapping_since.format("%d/%m/%Y %I:%M %p").to_string();
+            message.push_str(&format!("Flapping since: {}\n", since));
+        }
+
         let max_latency = endpoint.max_latency;
 
         if let Some(max_latency) = max_latency {
             message.push_str(&format!("Max latency: {}ms\n", max_latency));
         }
 
-        message.push_str("\n");
+        message.push('\n');
 
         db.endpoint.reset_max_latency(endpoint.url.as_str()).await?;
     }
 
-    message.push_str("\n");
+    message.push('\n');
 
     Ok(message)
 }
@@ -62,18 +90,28 @@ async fn server_update_message(db: &Db) -> anyhow::Result<String> {
 async fn incidents_update_message(db: &Arc<Db>) -> anyhow::Result<(String, Vec<String>)> {
     let mut message = String::new();
 
-    if !db.incident.is_empty().await? {
+    let incidents = db.incident.get_unreported().await?;
+
+    if !incidents.is_empty() {
         message.push_str("Incidents:\n\n");
     }
 
-    let incidents = db.incident.get_all().await?;
     for (i, incident) in incidents.iter().enumerate() {
         let is_last = i == incidents.len() - 1;
         let time = incident.created_at.format("%d/%m/%Y %I:%M %p").to_string();
         message.push_str(&format!("Message: {}\nTime: {}\n", incident.message, time));
 
+        if let Some(failure) = incident.failure {
+            let cause = failure.describe(incident.failure_detail.as_deref());
+            message.push_str(&format!("Cause: {}\n", cause));
+        }
+
+        if incident.maintenance {
+            message.push_str("During maintenance\n");
+        }
+
         if !is_last {
-            message.push_str("\n");
+            message.push('\n');
         } else {
             message.push_str("\n\n");
         }
@@ -87,19 +125,81 @@ async fn incidents_update_message(db: &Arc<Db>) -> anyhow::Result<(String, Vec<S
     Ok((message, ids))
 }
 
+/// Lists the certificates that expire soon or have an invalid chain
+async fn certificates_update_message(db: &Db) -> anyhow::Result<String> {
+    let max_days = get_cert_warn_days().into_iter().max().unwrap_or(0);
+    let certificates = db
+        .certificate
+        .get_all()
+        .await?
+        .into_iter()
+        .filter(|cert| cert.days_left() <= max_days || !cert.chain_valid)
+        .collect::<Vec<_>>();
+
+    if certificates.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut message = String::from("Certificates:\n\n");
+
+    for cert in certificates.iter() {
+        let url = Url::from(cert.url.clone());
+        let expires_at = cert.expires_at.format("%d/%m/%Y").to_string();
+
+        message.push_str(&format!(
+            "URL: {}\nExpires: {} ({} days)\nIssuer: {}\n",
+            url.strip_prefix(),
+            expires_at,
+            cert.days_left(),
+            cert.issuer
+        ));
+
+        if !cert.chain_valid {
+            let error = cert.error.as_deref().unwrap_or("unknown error");
+            message.push_str(&format!("Chain: ❌ {}\n", error));
+        }
+
+        message.push('\n');
+    }
+
+    message.push('\n');
+
+    Ok(message)
+}
+
 pub async fn create_server_update_cron(db: Arc<Db>, bot: Arc<Bot>) -> anyhow::Result<()> {
-    let interval = db.metadata.interval().await?;
+    let mut interval = db.metadata.interval().await?;
     tokio::spawn(async move {
-        tokio::time::sleep(Duration::from_millis(interval)).await;
         loop {
-            let result = server_update(&db, &bot).await;
+            tokio::time::sleep(Duration::from_millis(interval)).await;
+
+            if !LEADER.is_leader() {
+                // Follows the leader's digests to send the next one on time
+                // after taking over
+                interval = match db.metadata.interval().await {
+                    Ok(interval) => interval.max(get_leader_lease()),
+                    Err(e) => {
+                        METRICS.record_error(&e);
+                        error!(error = %e, "Couldn't get the digest interval");
+                        get_leader_lease()
+                    }
+                };
+                continue;
+            }
+
+            let Some(result) = track(server_update(&db, &bot)).await else {
+                break;
+            };
 
             match result {
                 Ok(_) => {}
-                Err(e) => eprintln!("Server update Error: {}", e),
+                Err(e) => {
+                    METRICS.record_error(&e);
+                    error!(error = %e, "Couldn't send the server update");
+                }
             }
 
-            tokio::time::sleep(Duration::from_millis(UPDATE_INTERVAL)).await;
+            interval = UPDATE_INTERVAL;
         }
     });
 
@@ -107,39 +207,727 @@ pub async fn create_server_update_cron(db: Arc<Db>, bot: Arc<Bot>) -> anyhow::Re
 }
 
 async fn server_update(db: &Arc<Db>, bot: &Arc<Bot>) -> anyhow::Result<()> {
-    let status_message = server_update_message(&db).await?;
-    let (incidents_message, ids) = incidents_update_message(&db).await?;
-    let message = format!("{}{}", incidents_message, status_message);
+    let status_message = server_update_message(db).await?;
+    let (incidents_message, ids) = incidents_update_message(db).await?;
+    let certificates_message = certificates_update_message(db).await?;
+    let message = format!(
+        "{}{}{}",
+        incidents_message, certificates_message, status_message
+    );
     let incidents: Vec<_> = ids.iter().map(|id| id.as_ref()).collect();
 
-    notify(&NotifyOpts { bot: &bot, message }).await?;
+    notify(&NotifyOpts { bot, message }).await?;
     db.metadata.update_last_sent_at().await?;
-    db.incident.delete_many(incidents).await?;
+    db.incident.mark_reported(incidents).await?;
+
+    let now = Local::now().naive_local();
+    let retention = chrono::Duration::days(CHECK_RETENTION_DAYS);
+    db.check_result.delete_before(now - retention).await?;
+
+    let retention = chrono::Duration::days(INCIDENT_RETENTION_DAYS);
+    db.incident.delete_before(now - retention).await?;
 
     Ok(())
 }
 
-pub async fn check_url_status(url: &Url, bot: &Bot, db: &Arc<Db>) -> anyhow::Result<()> {
-    let is_success = db.endpoint.lookup(url).await?;
+/// `urls` are all the monitored URLs, used to resolve the dependencies
+pub async fn check_url_status(
+    url: &Url,
+    urls: &[Url],
+    bot: &Bot,
+    db: &Arc<Db>,
+) -> anyhow::Result<()> {
+    let mut lookup = db.endpoint.lookup(url).await?;
+
+    // The leader decides the status, the standby's checks count as a location
+    if !LEADER.is_leader() {
+        quorum::record(url, &lookup, db).await?;
+        record_lookup(url, &lookup);
+        return Ok(());
+    }
+
+    quorum::apply(url, &mut lookup, db).await?;
+    let is_success = lookup.is_success;
+    record_lookup(url, &lookup);
+    db.endpoint.record_check(url, is_success).await?;
     let endpoint = db.endpoint.get(url).await?;
+    let in_maintenance = is_in_maintenance(url, Local::now().naive_local());
+    db.check_result.add(url, &lookup, in_maintenance).await?;
+
+    if let Some(answers) = &lookup.answers {
+        check_dns_answers(url, answers, bot, db).await?;
+    }
+
+    update_status(url, urls, &lookup, &endpoint, in_maintenance, bot, db).await?;
+
+    let status = db.endpoint.get(url).await?.status;
+    publish_check(url, is_success, lookup.latency, endpoint.status, status);
+    telemetry::record_check(url, is_success, lookup.latency, status);
+    Span::current().record("status", String::from(status));
+
+    match (endpoint.status, status) {
+        (previous, Status::Up) if previous != Status::Up => info!("Endpoint is up"),
+        (previous, Status::Down) if previous != Status::Down => warn!("Endpoint is down"),
+        _ => tracing::debug!(status = ?status, "Check completed"),
+    }
+
+    Ok(())
+}
 
-    if is_success && endpoint.status != Status::Up {
+fn record_lookup(url: &Url, lookup: &Lookup) {
+    METRICS.record_check(url, lookup.is_success, lookup.latency);
+
+    let outcome = if lookup.is_success {
+        "success"
+    } else {
+        "failure"
+    };
+    Span::current()
+        .record("outcome", outcome)
+        .record("latency", lookup.latency);
+}
+
+/// Applies the thresholds, dependencies and flap detection to the check and
+/// sends the alerts
+async fn update_status(
+    url: &Url,
+    urls: &[Url],
+    lookup: &Lookup,
+    endpoint: &Endpoint,
+    in_maintenance: bool,
+    bot: &Bot,
+    db: &Arc<Db>,
+) -> anyhow::Result<()> {
+    let is_success = lookup.is_success;
+
+    if is_success && endpoint.status != Status::Up && endpoint.successes >= get_up_threshold() {
         db.set_status_up(url).await?;
-        if endpoint.status == Status::Down {
+
+        // No alert was sent when it went down with its parent
+        let is_muted = in_maintenance
+            || endpoint.unreachable_via.is_some()
+            || detect_flapping(url, Status::Up, bot, db).await?;
+
+        if endpoint.status == Status::Down && !is_muted {
             notify(&NotifyOpts {
                 message: format!("✅ {} is up again!", url.strip_prefix()),
                 bot,
             })
             .await?;
         }
-    } else if !is_success && endpoint.status != Status::Down {
-        db.set_status_down(url).await?;
+    } else if !is_success
+        && endpoint.status != Status::Down
+        && endpoint.failures >= get_down_threshold()
+    {
+        // The failure is folded into the alert of the parent that's down
+        if let Some(parent) = down_parent(url, urls, db).await? {
+            db.set_status_unreachable(url, lookup, parent, in_maintenance)
+                .await?;
+            return Ok(());
+        }
+
+        // Held until the parent reaches its own threshold or recovers, so a
+        // child checked first in a cycle isn't alerted on its own
+        if let Some(parent) = failing_parent(url, urls, db).await? {
+            tracing::debug!(parent = parent.name(), "Waiting for the failing parent");
+            return Ok(());
+        }
+
+        db.set_status_down(url, lookup, in_maintenance).await?;
+        let is_muted = in_maintenance || detect_flapping(url, Status::Down, bot, db).await?;
+
+        if !is_muted {
+            notify_down(url, urls, lookup, bot, db).await?;
+        }
+    } else if !is_success && endpoint.unreachable_via.is_some() {
+        // The parent recovered but this endpoint is still down
+        if down_parent(url, urls, db).await?.is_none() {
+            db.set_status_down_from_unreachable(url, lookup, in_maintenance)
+                .await?;
+            let is_muted = in_maintenance || detect_flapping(url, Status::Down, bot, db).await?;
+
+            if !is_muted {
+                notify_down(url, urls, lookup, bot, db).await?;
+            }
+        }
+    } else if let Some(flapping_since) = endpoint.flapping_since {
+        check_flapping_stopped(url, &endpoint.status, flapping_since, bot, db).await?;
+    }
+
+    Ok(())
+}
+
+/// Returns the first endpoint the URL depends on that is down
+async fn down_parent<'a>(url: &Url, urls: &'a [Url], db: &Db) -> anyhow::Result<Option<&'a Url>> {
+    for parent in parents(url, urls) {
+        if db.endpoint.get(parent).await?.status == Status::Down {
+            return Ok(Some(parent));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns the first endpoint the URL depends on that isn't down yet but
+/// failed its last check, it decides whether the URL is unreachable
+async fn failing_parent<'a>(
+    url: &Url,
+    urls: &'a [Url],
+    db: &Db,
+) -> anyhow::Result<Option<&'a Url>> {
+    for parent in parents(url, urls) {
+        let endpoint = db.endpoint.get(parent).await?;
+        if endpoint.status != Status::Down && endpoint.failures > 0 {
+            return Ok(Some(parent));
+        }
+    }
+
+    Ok(None)
+}
+
+/// The endpoints depending on the URL that are unreachable through it, or
+/// failing and about to be marked so on their next check
+async fn affected_dependents<'a>(
+    url: &Url,
+    urls: &'a [Url],
+    db: &Db,
+) -> anyhow::Result<Vec<&'a Url>> {
+    let mut affected = Vec::new();
+    for dependent in dependents(url, urls) {
+        let endpoint = db.endpoint.get(dependent).await?;
+        if endpoint.unreachable_via.as_deref() == Some(url.as_str()) || endpoint.failures > 0 {
+            affected.push(dependent);
+        }
+    }
+
+    Ok(affected)
+}
+
+async fn notify_down(
+    url: &Url,
+    urls: &[Url],
+    lookup: &Lookup,
+    bot: &Bot,
+    db: &Db,
+) -> anyhow::Result<()> {
+    let message = down_message(url, urls, lookup, db).await?;
+    notify(&NotifyOpts { message, bot }).await?;
+
+    Ok(())
+}
+
+/// The down alert lists the affected dependents since their own failures
+/// won't be alerted
+async fn down_message(url: &Url, urls: &[Url], lookup: &Lookup, db: &Db) -> anyhow::Result<String> {
+    let mut message = format!("❌ {} is down!", url.strip_prefix());
+
+    let dependents = affected_dependents(url, urls, db).await?;
+    if !dependents.is_empty() {
+        let names = dependents
+            .iter()
+            .map(|url| url.name())
+            .collect::<Vec<_>>()
+            .join(", ");
+        message.push_str(&format!("\nAffected: {}", names));
+    }
+
+    if !lookup.failing_locations.is_empty() {
+        message.push_str(&format!(
+            "\nFailing from: {}",
+            lookup.failing_locations.join(", ")
+        ));
+    }
+
+    if let Some(failure) = lookup.failure {
+        message.push_str(&format!(
+            "\nCause: {}",
+            failure.describe(lookup.detail.as_deref())
+        ));
+    }
+
+    if let Some(timings) = lookup.timings.as_ref().filter(|t| !t.is_empty()) {
+        message.push_str(&format!("\nTimings: {}", timings));
+    }
+
+    if let Some(snapshot) = &lookup.snapshot {
+        message.push_str(&format!(
+            "\nResponse: {} from {}",
+            snapshot.status_line, snapshot.remote_ip
+        ));
+
+        let excerpt = snapshot
+            .body
+            .trim()
+            .chars()
+            .take(ALERT_BODY_EXCERPT_LENGTH)
+            .collect::<String>();
+        if !excerpt.is_empty() {
+            message.push_str(&format!("\n\n{}", excerpt));
+        }
+    }
+
+    Ok(message)
+}
+
+/// Records a status change and returns `true` while the endpoint is
+/// flapping, individual up/down alerts are skipped in that case
+async fn detect_flapping(url: &Url, status: Status, bot: &Bot, db: &Db) -> anyhow::Result<bool> {
+    db.status_change.add(url, status).await?;
+
+    let threshold = get_flap_threshold();
+    if threshold == 0 {
+        return Ok(false);
+    }
+
+    if db.endpoint.get(url).await?.flapping_since.is_some() {
+        return Ok(true);
+    }
+
+    let now = Local::now().naive_local();
+    let window = chrono::Duration::milliseconds(get_flap_window() as i64);
+    let changes = db.status_change.count_since(url, now - window).await?;
+
+    if changes < threshold {
+        return Ok(false);
+    }
+
+    db.endpoint.set_flapping_since(url, Some(now)).await?;
+    notify(&NotifyOpts {
+        message: format!(
+            "🔁 {} is flapping, its status changed {} times in the last {} minutes. Alerts are paused until it's stable.",
+            url.strip_prefix(),
+            changes,
+            window.num_minutes()
+        ),
+        bot,
+    })
+    .await?;
+
+    Ok(true)
+}
+
+/// Ends the flapping state once the status hasn't changed for a whole flap
+/// window and sends a summary
+async fn check_flapping_stopped(
+    url: &Url,
+    status: &Status,
+    flapping_since: NaiveDateTime,
+    bot: &Bot,
+    db: &Db,
+) -> anyhow::Result<()> {
+    let now = Local::now().naive_local();
+    let window = chrono::Duration::milliseconds(get_flap_window() as i64);
+
+    if db.status_change.count_since(url, now - window).await? > 0 {
+        return Ok(());
+    }
+
+    let changes = db
+        .status_change
+        .count_since(url, flapping_since - window)
+        .await?;
+    db.endpoint.set_flapping_since(url, None).await?;
+
+    let emoji = match status {
+        Status::Up => "✅",
+        Status::Down => "❌",
+        Status::Pending => "🕒",
+    };
+
+    notify(&NotifyOpts {
+        message: format!(
+            "{} {} stopped flapping and is {:?}. Its status changed {} times while flapping.",
+            emoji,
+            url.strip_prefix(),
+            status,
+            changes
+        ),
+        bot,
+    })
+    .await?;
+
+    Ok(())
+}
+
+pub fn create_cert_check_cron(urls: watch::Receiver<Arc<Vec<Url>>>, db: Arc<Db>, bot: Arc<Bot>) {
+    let interval = get_cert_check_interval();
+
+    tokio::spawn(async move {
+        loop {
+            let urls = urls.borrow().clone();
+
+            // The standby would warn about the same certificates
+            if LEADER.is_leader() {
+                let checks = async {
+                    for url in urls.iter().filter(|url| url.tls_address().is_some()) {
+                        if let Err(e) = check_certificate(url, &bot, &db).await {
+                            METRICS.record_error(&e);
+                            error!(url = url.as_str(), error = %e, "Certificate check failed");
+                        }
+                    }
+                };
+
+                if track(checks).await.is_none() {
+                    break;
+                }
+            }
+
+            tokio::time::sleep(Duration::from_millis(interval)).await;
+        }
+    });
+}
+
+/// Records the certificate of the URL and warns when it's about to expire
+/// or when its chain becomes invalid
+pub async fn check_certificate(url: &Url, bot: &Bot, db: &Db) -> anyhow::Result<()> {
+    let info = db.endpoint.certificate(url).await?;
+    let previous = db.certificate.get(url).await?;
+    db.certificate.upsert(url, &info).await?;
+
+    if !info.chain_valid && previous.is_none_or(|cert| cert.chain_valid) {
+        let error = info.error.as_deref().unwrap_or("unknown error");
         notify(&NotifyOpts {
-            message: format!("❌ {} is down!", url.strip_prefix()),
+            message: format!(
+                "⚠️ {} has an invalid certificate chain: {}",
+                url.strip_prefix(),
+                error
+            ),
             bot,
         })
         .await?;
     }
 
+    let Some(cert) = db.certificate.get(url).await? else {
+        return Ok(());
+    };
+
+    let days_left = cert.days_left();
+    if let Some(days) = warn_threshold(
+        days_left,
+        cert.is_expired(),
+        &get_cert_warn_days(),
+        cert.warned_days,
+    ) {
+        let message = if days == EXPIRED {
+            format!("❌ The certificate of {} has expired!", url.strip_prefix())
+        } else {
+            format!(
+                "⚠️ The certificate of {} expires in {} days ({})",
+                url.strip_prefix(),
+                days_left,
+                cert.expires_at.format("%d/%m/%Y")
+            )
+        };
+
+        notify(&NotifyOpts { message, bot }).await?;
+        db.certificate.set_warned_days(url, days).await?;
+    }
+
+    Ok(())
+}
+
+/// Stores the answers of a DNS monitor and notifies when they changed since
+/// the last check
+async fn check_dns_answers(
+    url: &Url,
+    answers: &[String],
+    bot: &Bot,
+    db: &Db,
+) -> anyhow::Result<()> {
+    let previous = db.dns_answer.get(url).await?;
+    db.dns_answer.set(url, answers).await?;
+
+    let Some(previous) = previous else {
+        return Ok(());
+    };
+
+    if previous != answers {
+        let format_answers = |answers: &[String]| {
+            if answers.is_empty() {
+                "(empty)".to_string()
+            } else {
+                answers.join(", ")
+            }
+        };
+
+        notify(&NotifyOpts {
+            message: format!(
+                "🔀 The DNS answer of {} changed\nBefore: {}\nNow: {}",
+                url.strip_prefix(),
+                format_answers(&previous),
+                format_answers(answers)
+            ),
+            bot,
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Notifies when a maintenance window starts and ends, the status of the
+/// affected endpoints is included when it ends since their alerts were muted
+pub fn create_maintenance_cron(urls: watch::Receiver<Arc<Vec<Url>>>, db: Arc<Db>, bot: Arc<Bot>) {
+    let windows = get_maintenance_windows();
+    if windows.is_empty() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        // Windows that are already running on startup were announced before
+        let now = Local::now().naive_local();
+        let mut active = (0..windows.len())
+            .filter(|&i| windows[i].is_active(now))
+            .collect::<HashSet<_>>();
+
+        loop {
+            tokio::time::sleep(Duration::from_millis(MAINTENANCE_CHECK_INTERVAL)).await;
+            let update = async {
+                let now = Local::now().naive_local();
+                let urls = urls.borrow().clone();
+
+                for (i, window) in windows.iter().enumerate() {
+                    let urls = urls
+                        .iter()
+                        .filter(|url| window.applies_to(url))
+                        .collect::<Vec<_>>();
+                    let names = urls
+                        .iter()
+                        .map(|url| url.name())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+
+                    let mut still_down = Vec::new();
+                    let message = match window.active_since(now) {
+                        Some(start) if active.insert(i) => format!(
+                            "🛠 Maintenance started for {} until {}",
+                            names,
+                            window.end(start).format("%d/%m/%Y %I:%M %p")
+                        ),
+                        None if active.remove(&i) => {
+                            let mut message = format!("🛠 Maintenance ended for {}\n", names);
+                            for url in urls.iter() {
+                                match db.endpoint.get(url).await {
+                                    Ok(endpoint) => {
+                                        message.push_str(&format!(
+                                            "\n{}: {:?}",
+                                            url.name(),
+                                            endpoint.status
+                                        ));
+
+                                        if endpoint.status == Status::Down {
+                                            still_down.push(*url);
+                                        }
+                                    }
+                                    Err(e) => {
+                                        METRICS.record_error(&e);
+                                        error!(error = %e, "Couldn't get the endpoint status");
+                                    }
+                                }
+                            }
+                            message
+                        }
+                        _ => continue,
+                    };
+
+                    // The windows are still tracked so a new leader doesn't
+                    // announce them again
+                    if !LEADER.is_leader() {
+                        continue;
+                    }
+
+                    if let Err(e) = notify(&NotifyOpts { message, bot: &bot }).await {
+                        error!(error = %e, "Couldn't send the maintenance notice");
+                    }
+
+                    for url in still_down {
+                        if let Err(e) = notify_down_after_maintenance(url, now, &bot, &db).await {
+                            METRICS.record_error(&e);
+                            error!(error = %e, "Couldn't send the down alert");
+                        }
+                    }
+                }
+            };
+
+            if track(update).await.is_none() {
+                break;
+            }
+        }
+    });
+}
+
+/// The down alert of an endpoint that went down during a maintenance window
+/// was muted, it's sent when the window ends and the endpoint is still down
+async fn notify_down_after_maintenance(
+    url: &Url,
+    now: NaiveDateTime,
+    bot: &Bot,
+    db: &Db,
+) -> anyhow::Result<()> {
+    // Another window still covers it, or the alert is up to its parent or
+    // paused while flapping
+    let endpoint = db.endpoint.get(url).await?;
+    if is_in_maintenance(url, now)
+        || endpoint.unreachable_via.is_some()
+        || endpoint.flapping_since.is_some()
+    {
+        return Ok(());
+    }
+
+    // It was down before the window started and was alerted then
+    let Some(incident) = db.incident.get_open(url).await? else {
+        return Ok(());
+    };
+    if !incident.maintenance {
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "❌ {} is still down after the maintenance!",
+        url.strip_prefix()
+    );
+    if let Some(failure) = incident.failure {
+        message.push_str(&format!(
+            "\nCause: {}",
+            failure.describe(incident.failure_detail.as_deref())
+        ));
+    }
+
+    notify(&NotifyOpts { message, bot }).await?;
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{endpoint::Failure, incident::IncidentFilter};
+
+    fn urls() -> Vec<Url> {
+        [
+            "https://gateway.example.com#name=gateway",
+            "https://api.example.com#name=api&depends=gateway",
+            "https://web.example.com#name=web&depends=gateway",
+        ]
+        .into_iter()
+        .map(|url| Url::from(url.to_string()))
+        .collect()
+    }
+
+    fn failing() -> Lookup {
+        Lookup {
+            is_success: false,
+            ..Default::default()
+        }
+    }
+
+    async fn fail_check(url: &Url, urls: &[Url], in_maintenance: bool, bot: &Bot, db: &Arc<Db>) {
+        db.endpoint.record_check(url, false).await.unwrap();
+        let endpoint = db.endpoint.get(url).await.unwrap();
+        update_status(url, urls, &failing(), &endpoint, in_maintenance, bot, db)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_parent_down_marks_the_child_unreachable() {
+        let urls = urls();
+        let (gateway, api, web) = (&urls[0], &urls[1], &urls[2]);
+        let db = Arc::new(Db::in_memory(&urls).await.unwrap());
+        let bot = Bot::new("token");
+
+        db.set_status_up(web).await.unwrap();
+        db.set_status_down(gateway, &failing(), false)
+            .await
+            .unwrap();
+        fail_check(api, &urls, false, &bot, &db).await;
+
+        let endpoint = db.endpoint.get(api).await.unwrap();
+        assert_eq!(endpoint.status, Status::Down);
+        assert_eq!(endpoint.unreachable_via.as_deref(), Some(gateway.as_str()));
+
+        let incident = db.incident.get_open(api).await.unwrap().unwrap();
+        assert!(incident.message.contains("unreachable"));
+
+        // The web endpoint passes its own checks
+        let affected = affected_dependents(gateway, &urls, &db).await.unwrap();
+        let names = affected.iter().map(|url| url.name()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["api"]);
+    }
+
+    #[tokio::test]
+    async fn test_down_alert_lists_the_failing_dependents() {
+        let urls = urls();
+        let (gateway, api, web) = (&urls[0], &urls[1], &urls[2]);
+        let db = Arc::new(Db::in_memory(&urls).await.unwrap());
+
+        // The api failed its check but isn't marked unreachable yet
+        db.endpoint.record_check(api, false).await.unwrap();
+        db.endpoint.record_check(web, true).await.unwrap();
+
+        let lookup = Lookup {
+            failure: Some(Failure::Timeout),
+            ..failing()
+        };
+        let message = down_message(gateway, &urls, &lookup, &db).await.unwrap();
+
+        assert_eq!(
+            message,
+            "❌ gateway.example.com is down!\nAffected: api\nCause: Timeout"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_child_waits_for_the_failing_parent() {
+        let urls = urls();
+        let (gateway, api) = (&urls[0], &urls[1]);
+        let db = Arc::new(Db::in_memory(&urls).await.unwrap());
+        let bot = Bot::new("token");
+
+        // Checked before its parent in the same cycle
+        db.endpoint.record_check(gateway, false).await.unwrap();
+        fail_check(api, &urls, false, &bot, &db).await;
+
+        assert_eq!(db.endpoint.get(api).await.unwrap().status, Status::Pending);
+        assert!(db.incident.get_open(api).await.unwrap().is_none());
+
+        db.set_status_down(gateway, &failing(), false)
+            .await
+            .unwrap();
+        fail_check(api, &urls, false, &bot, &db).await;
+
+        let endpoint = db.endpoint.get(api).await.unwrap();
+        assert_eq!(endpoint.status, Status::Down);
+        assert_eq!(endpoint.unreachable_via.as_deref(), Some(gateway.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_parent_recovers_while_the_child_is_still_down() {
+        let urls = urls();
+        let (gateway, api) = (&urls[0], &urls[1]);
+        let db = Arc::new(Db::in_memory(&urls).await.unwrap());
+        let bot = Bot::new("token");
+
+        db.set_status_down(gateway, &failing(), false)
+            .await
+            .unwrap();
+        fail_check(api, &urls, false, &bot, &db).await;
+        db.set_status_up(gateway).await.unwrap();
+
+        // In maintenance so the down alert isn't sent
+        fail_check(api, &urls, true, &bot, &db).await;
+
+        let endpoint = db.endpoint.get(api).await.unwrap();
+        assert_eq!(endpoint.status, Status::Down);
+        assert!(endpoint.unreachable_via.is_none());
+
+        let filter = IncidentFilter {
+            url: Some(api.as_str().to_string()),
+            ..Default::default()
+        };
+        let incidents = db.incident.find(&filter).await.unwrap();
+        assert_eq!(incidents.len(), 1);
+        assert!(incidents[0].message.contains("was down"));
+        assert!(incidents[0].resolved_at.is_none());
+    }
+}
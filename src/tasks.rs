@@ -0,0 +1,16 @@
+use std::{future::Future, sync::LazyLock};
+use tokio_util::task::TaskTracker;
+
+/// The checks and the crons' work that write to the db. It's closed when the
+/// shutdown starts and the db is closed once it's empty
+pub static TASKS: LazyLock<TaskTracker> = LazyLock::new(TaskTracker::new);
+
+/// Runs the work as part of `TASKS`, or skips it with `None` when the monitor
+/// is shutting down
+pub async fn track<F: Future>(work: F) -> Option<F::Output> {
+    if TASKS.is_closed() {
+        return None;
+    }
+
+    Some(TASKS.track_future(work).await)
+}
@@ -0,0 +1,132 @@
+use opentelemetry::{
+    global,
+    metrics::{Counter, Gauge, Histogram},
+    trace::TracerProvider as _,
+    KeyValue,
+};
+use opentelemetry_otlp::{MetricExporter, SpanExporter};
+use opentelemetry_sdk::{
+    metrics::{PeriodicReader, SdkMeterProvider},
+    propagation::TraceContextPropagator,
+    runtime,
+    trace::{Tracer, TracerProvider},
+    Resource,
+};
+use std::sync::LazyLock;
+use tracing::error;
+
+use crate::{
+    constants::{get_otel_service_name, get_otlp_endpoint},
+    db::{endpoint::Status, url::Url},
+};
+
+const INSTRUMENTATION_SCOPE: &str = "server-monitor";
+
+/// Created on the first check, after the meter provider is set, the
+/// instruments are no-ops while OTLP export is disabled
+static INSTRUMENTS: LazyLock<Instruments> = LazyLock::new(Instruments::new);
+
+/// The OTLP exporters, set up when `OTEL_EXPORTER_OTLP_ENDPOINT` is set. The
+/// endpoint and the `OTEL_EXPORTER_OTLP_*` settings are read by the exporters
+pub struct Telemetry {
+    tracer_provider: TracerProvider,
+    meter_provider: SdkMeterProvider,
+}
+
+impl Telemetry {
+    pub fn init() -> anyhow::Result<Option<Self>> {
+        if get_otlp_endpoint().is_none() {
+            return Ok(None);
+        }
+
+        let resource = Resource::new([KeyValue::new("service.name", get_otel_service_name())]);
+
+        let tracer_provider = TracerProvider::builder()
+            .with_batch_exporter(SpanExporter::builder().with_http().build()?, runtime::Tokio)
+            .with_resource(resource.clone())
+            .build();
+
+        let reader = PeriodicReader::builder(
+            MetricExporter::builder().with_http().build()?,
+            runtime::Tokio,
+        )
+        .build();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(reader)
+            .with_resource(resource)
+            .build();
+
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        global::set_tracer_provider(tracer_provider.clone());
+        global::set_meter_provider(meter_provider.clone());
+
+        Ok(Some(Self {
+            tracer_provider,
+            meter_provider,
+        }))
+    }
+
+    pub fn tracer(&self) -> Tracer {
+        self.tracer_provider.tracer(INSTRUMENTATION_SCOPE)
+    }
+
+    /// Exports what's left before the monitor exits
+    pub fn shutdown(self) {
+        if let Err(e) = self.tracer_provider.shutdown() {
+            error!(error = %e, "Couldn't export the remaining traces");
+        }
+        if let Err(e) = self.meter_provider.shutdown() {
+            error!(error = %e, "Couldn't export the remaining metrics");
+        }
+    }
+}
+
+struct Instruments {
+    duration: Histogram<f64>,
+    checks: Counter<u64>,
+    up: Gauge<i64>,
+}
+
+impl Instruments {
+    fn new() -> Self {
+        let meter = global::meter(INSTRUMENTATION_SCOPE);
+
+        Self {
+            duration: meter
+                .f64_histogram("check.duration")
+                .with_unit("s")
+                .with_description("Latency of the checks")
+                .build(),
+            checks: meter
+                .u64_counter("checks")
+                .with_description("Checks by outcome")
+                .build(),
+            up: meter
+                .i64_gauge("endpoint.up")
+                .with_description("1 if the endpoint is up, 0 if it's down")
+                .build(),
+        }
+    }
+}
+
+/// Records the check on the OTLP metrics, `latency` is in milliseconds
+pub fn record_check(url: &Url, success: bool, latency: Option<i64>, status: Status) {
+    let endpoint = KeyValue::new("endpoint", url.name().to_string());
+    let outcome = if success { "success" } else { "failure" };
+
+    INSTRUMENTS
+        .checks
+        .add(1, &[endpoint.clone(), KeyValue::new("outcome", outcome)]);
+
+    if let Some(latency) = latency {
+        INSTRUMENTS
+            .duration
+            .record(latency as f64 / 1000.0, std::slice::from_ref(&endpoint));
+    }
+
+    match status {
+        Status::Up => INSTRUMENTS.up.record(1, &[endpoint]),
+        Status::Down => INSTRUMENTS.up.record(0, &[endpoint]),
+        Status::Pending => {}
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Local, NaiveDateTime};
+use std::{
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    rustls::{
+        client::{
+            danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+            WebPkiServerVerifier,
+        },
+        crypto::ring,
+        pki_types::{CertificateDer, ServerName, UnixTime},
+        ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    },
+    TlsConnector,
+};
+use tracing::warn;
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
+
+/// The system's trusted roots, the same ones the HTTP checks verify against
+/// so a private CA installed on the host is trusted by both
+static ROOTS: LazyLock<Arc<RootCertStore>> = LazyLock::new(|| {
+    let native = rustls_native_certs::load_native_certs();
+    for error in &native.errors {
+        warn!(error = %error, "Couldn't load a system root certificate");
+    }
+
+    let mut roots = RootCertStore::empty();
+    let (_, ignored) = roots.add_parsable_certificates(native.certs);
+    if ignored > 0 {
+        warn!(ignored, "Some system root certificates couldn't be parsed");
+    }
+
+    Arc::new(roots)
+});
+
+/// Details about the leaf certificate presented by a TLS server
+#[derive(Debug)]
+pub struct CertificateInfo {
+    pub expires_at: NaiveDateTime,
+    pub issuer: String,
+    pub sans: Vec<String>,
+    pub chain_valid: bool,
+    /// Why the chain failed verification, if it did
+    pub error: Option<String>,
+}
+
+/// Accepts any certificate during the handshake so the chain can still be
+/// inspected when it's invalid, the chain is verified after the handshake.
+#[derive(Debug)]
+struct AcceptAnyCertificate(Arc<WebPkiServerVerifier>);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        self.0.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        self.0.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.supported_verify_schemes()
+    }
+}
+
+/// Connects to `host:port`, completes a TLS handshake and returns the
+/// details of the leaf certificate along with the chain validity.
+pub async fn inspect(host: &str, port: u16, timeout: Duration) -> anyhow::Result<CertificateInfo> {
+    let provider = Arc::new(ring::default_provider());
+    let verifier =
+        WebPkiServerVerifier::builder_with_provider(Arc::clone(&ROOTS), Arc::clone(&provider))
+            .build()?;
+
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(Arc::clone(&verifier))))
+        .with_no_client_auth();
+    let connector = TlsConnector::from(Arc::new(config));
+    let server_name = ServerName::try_from(host.to_string())?;
+
+    let stream = tokio::time::timeout(timeout, async {
+        let tcp = TcpStream::connect((host, port)).await?;
+        connector.connect(server_name.clone(), tcp).await
+    })
+    .await??;
+
+    let (_, connection) = stream.get_ref();
+    let (leaf, intermediates) = connection
+        .peer_certificates()
+        .and_then(|certs| certs.split_first())
+        .ok_or_else(|| anyhow!("{}:{} didn't present a certificate", host, port))?;
+
+    let verification =
+        verifier.verify_server_cert(leaf, intermediates, &server_name, &[], UnixTime::now());
+
+    let (_, cert) = X509Certificate::from_der(leaf)?;
+
+    let expires_at = DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0)
+        .ok_or_else(|| anyhow!("Invalid certificate expiry"))?
+        .with_timezone(&Local)
+        .naive_local();
+
+    let sans = match cert.subject_alternative_name()? {
+        Some(extension) => extension
+            .value
+            .general_names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::DNSName(name) => Some(name.to_string()),
+                GeneralName::IPAddress(_) => Some(name.to_string()),
+                _ => None,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    Ok(CertificateInfo {
+        expires_at,
+        issuer: cert.issuer().to_string(),
+        sans,
+        chain_valid: verification.is_ok(),
+        error: verification.err().map(|e| e.to_string()),
+    })
+}
+
+/// Stored as the warned threshold once the expiry was announced, it's below
+/// every warning day so nothing is sent after it
+pub const EXPIRED: i64 = -1;
+
+/// Returns the threshold (in days) that should be warned about now, if any.
+/// An expired certificate reaches the `EXPIRED` threshold.
+///
+/// `thresholds` are the configured warning days, `warned` is the last
+/// threshold a warning was already sent for.
+pub fn warn_threshold(
+    days_left: i64,
+    expired: bool,
+    thresholds: &[i64],
+    warned: Option<i64>,
+) -> Option<i64> {
+    let threshold = if expired {
+        EXPIRED
+    } else {
+        thresholds
+            .iter()
+            .filter(|&&t| days_left <= t)
+            .min()
+            .copied()?
+    };
+
+    match warned {
+        Some(warned) if warned <= threshold => None,
+        _ => Some(threshold),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warn_threshold() {
+        let thresholds = [30, 14, 7, 1];
+
+        assert_eq!(warn_threshold(45, false, &thresholds, None), None);
+        assert_eq!(warn_threshold(30, false, &thresholds, None), Some(30));
+        assert_eq!(warn_threshold(20, false, &thresholds, Some(30)), None);
+        assert_eq!(warn_threshold(13, false, &thresholds, Some(30)), Some(14));
+        assert_eq!(warn_threshold(3, false, &thresholds, None), Some(7));
+        assert_eq!(warn_threshold(0, false, &thresholds, Some(7)), Some(1));
+        assert_eq!(warn_threshold(0, false, &thresholds, Some(1)), None);
+    }
+
+    #[test]
+    fn test_warn_threshold_expired() {
+        let thresholds = [30, 14, 7, 1];
+
+        // Less than a day past the expiry still counts as 0 days left
+        assert_eq!(warn_threshold(0, true, &thresholds, Some(1)), Some(EXPIRED));
+        assert_eq!(
+            warn_threshold(-2, true, &thresholds, Some(1)),
+            Some(EXPIRED)
+        );
+        assert_eq!(warn_threshold(-40, true, &thresholds, None), Some(EXPIRED));
+        assert_eq!(warn_threshold(-3, true, &thresholds, Some(EXPIRED)), None);
+    }
+}